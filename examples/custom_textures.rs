@@ -2,14 +2,15 @@ use std::error::Error;
 use std::io::Cursor;
 
 use vulkano::device::{Device, Queue};
-use vulkano::image::{ImmutableImage, Dimensions, MipmapsCount};
+use vulkano::image::{ImmutableImage, ImageDimensions, MipmapsCount};
+use vulkano::image::view::ImageView;
 use vulkano::sampler::Sampler;
 use vulkano::format::Format;
 use vulkano::sync::GpuFuture;
 
 use image::{jpeg::JpegDecoder, ImageDecoder};
 use imgui::*;
-use imgui_vulkano_renderer::Texture;
+use imgui_vulkano_renderer::{BlendMode, Texture};
 
 use std::sync::Arc;
 
@@ -46,13 +47,13 @@ impl CustomTexturesApp {
                     data.push(i as u8);
                     data.push(j as u8);
                     data.push((i + j) as u8);
-                    data.push((255) as u8);
+                    data.push(255_u8);
                 }
             }
 
             let (texture, fut) = ImmutableImage::from_iter(
                 data.iter().cloned(),
-                Dimensions::Dim2d{width : WIDTH as u32, height : HEIGHT as u32},
+                ImageDimensions::Dim2d{width : WIDTH as u32, height : HEIGHT as u32, array_layers : 1},
                 MipmapsCount::One,
                 Format::R8G8B8A8Srgb,
                 queue.clone()
@@ -63,7 +64,7 @@ impl CustomTexturesApp {
             fut.then_signal_fence_and_flush().unwrap()
                 .wait(None).expect("Failed to load texture");
 
-            let texture_id = textures.insert((texture, sampler));
+            let texture_id = textures.insert(Texture::new(ImageView::new(texture)?, sampler));
 
             self.my_texture_id = Some(texture_id);
         }
@@ -115,7 +116,7 @@ impl Lenna {
 
         let (texture, fut) = ImmutableImage::from_iter(
             image_encoded.iter().cloned(),
-            Dimensions::Dim2d{width, height},
+            ImageDimensions::Dim2d{width, height, array_layers : 1},
             MipmapsCount::One,
             Format::R8G8B8A8Srgb,
             queue.clone()
@@ -126,7 +127,8 @@ impl Lenna {
         fut.then_signal_fence_and_flush().unwrap()
             .wait(None).expect("Failed to load texture");
 
-        let texture_id = textures.insert((texture, sampler));
+        // a JPEG has no alpha, so the texture is drawn as opaque
+        let texture_id = textures.insert(Texture::new(ImageView::new(texture)?, sampler).with_blend_mode(BlendMode::Opaque));
         Ok(Lenna {
             texture_id,
             size: [width as f32, height as f32],
@@ -1,7 +1,5 @@
 use imgui::{Context, FontConfig, FontGlyphRanges, FontSource, Ui};
 use imgui_winit_support::{HiDpiMode, WinitPlatform};
-use std::time::Instant;
-use std::time::Duration;
 
 use vulkano::command_buffer::AutoCommandBufferBuilder;
 use vulkano::image::view::ImageView;
@@ -27,7 +25,7 @@ use winit::window::{Window, WindowBuilder};
 
 use std::sync::Arc;
 
-use imgui_vulkano_renderer::Renderer;
+use imgui_vulkano_renderer::{AttachmentLoad, FontAtlasFormat, Renderer, RendererOptions};
 
 mod clipboard;
 
@@ -41,6 +39,7 @@ pub struct System {
     pub imgui: Context,
     pub platform: WinitPlatform,
     pub renderer: Renderer,
+    #[allow(dead_code)]
     pub font_size: f32,
 }
 
@@ -99,10 +98,7 @@ pub fn init(title: &str) -> System {
 
         let dimensions: [u32; 2] = surface.window().inner_size().into();
 
-        let image_usage = ImageUsage {
-            transfer_destination : true,
-            ..ImageUsage::color_attachment()
-        };
+        let image_usage = ImageUsage::color_attachment();
 
         Swapchain::start(device.clone(), surface.clone())
             .num_images(caps.min_image_count)
@@ -130,7 +126,7 @@ pub fn init(title: &str) -> System {
     }
 
     let mut platform = WinitPlatform::init(&mut imgui);
-    platform.attach_window(imgui.io_mut(), &surface.window(), HiDpiMode::Rounded);
+    platform.attach_window(imgui.io_mut(), surface.window(), HiDpiMode::Rounded);
 
     let hidpi_factor = platform.hidpi_factor();
     let font_size = (13.0 * hidpi_factor) as f32;
@@ -154,7 +150,15 @@ pub fn init(title: &str) -> System {
 
     imgui.io_mut().font_global_scale = (1.0 / hidpi_factor) as f32;
 
-    let renderer = Renderer::init(&mut imgui, device.clone(), queue.clone(), format).expect("Failed to initialize renderer");
+    // the UI is the only thing in the window, so the render pass clears the swapchain image itself
+    // the japanese glyph ranges make the atlas large, so store it with one byte per texel
+    let options = RendererOptions {
+        load : AttachmentLoad::Clear([0.0; 4]),
+        font_atlas : FontAtlasFormat::Alpha8,
+        ..RendererOptions::default()
+    };
+
+    let renderer = Renderer::init_with_options(&mut imgui, device.clone(), queue.clone(), format, options).expect("Failed to initialize renderer");
 
     System {
         event_loop,
@@ -189,33 +193,18 @@ impl System {
 
         let mut previous_frame_end = Some(sync::now(device.clone()).boxed());
 
-        let mut last_redraw = Instant::now();
-
-        // target 60 fps
-        let target_frame_time = Duration::from_millis(1000 / 60);
-
         event_loop.run(move |event, _, control_flow| match event {
             Event::NewEvents(_) => {
                 // imgui.io_mut().update_delta_time(Instant::now());
             }
             Event::MainEventsCleared => {
                 platform
-                    .prepare_frame(imgui.io_mut(), &surface.window())
+                    .prepare_frame(imgui.io_mut(), surface.window())
                     .expect("Failed to prepare frame");
                 surface.window().request_redraw();
             }
             Event::RedrawRequested(_) => {
-                
-                let t = Instant::now();
-                let since_last = t.duration_since(last_redraw);
-                last_redraw = t;
 
-                if since_last > target_frame_time {
-                    if since_last < target_frame_time {
-                        std::thread::sleep(target_frame_time - since_last);
-                    }
-                }
-                
                 previous_frame_end.as_mut().unwrap().cleanup_finished();
 
                 if recreate_swapchain {
@@ -229,6 +218,7 @@ impl System {
 
                     images = new_images;
                     swapchain = new_swapchain;
+                    renderer.invalidate_framebuffers();
                     recreate_swapchain = false;
                 }
 
@@ -260,9 +250,6 @@ impl System {
                 let mut cmd_buf_builder = AutoCommandBufferBuilder::primary(device.clone(), queue.family(), vulkano::command_buffer::CommandBufferUsage::OneTimeSubmit)
                     .expect("Failed to create command buffer");
 
-                cmd_buf_builder.clear_color_image(images[image_num].clone(), [0.0; 4].into())
-                    .expect("Failed to create image clear command");
-
                 renderer
                     .draw_commands(&mut cmd_buf_builder, queue.clone(), ImageView::new(images[image_num].clone()).unwrap(), draw_data)
                     .expect("Rendering failed");
@@ -82,11 +82,11 @@ impl Default for State {
             no_menu: false,
             no_close: false,
             wrap_width: 200.0,
-            buf: buf,
+            buf,
             item: 0,
             item2: 0,
-            text: text,
-            text_multiline: text_multiline,
+            text,
+            text_multiline,
             i0: 123,
             f0: 0.001,
             vec2f: [0.10, 0.20],
@@ -364,7 +364,7 @@ fn show_test_window(ui: &Ui, state: &mut State, opened: &mut bool) {
             menu_bar.end(ui);
         }
         ui.spacing();
-        if CollapsingHeader::new(im_str!("Help")).build(&ui) {
+        if CollapsingHeader::new(im_str!("Help")).build(ui) {
             ui.text_wrapped(im_str!(
                 "This window is being created by the show_test_window() \
                  function. Please refer to the code for programming \
@@ -373,7 +373,7 @@ fn show_test_window(ui: &Ui, state: &mut State, opened: &mut bool) {
             show_user_guide(ui);
         }
 
-        if CollapsingHeader::new(im_str!("Window options")).build(&ui) {
+        if CollapsingHeader::new(im_str!("Window options")).build(ui) {
             ui.checkbox(im_str!("No titlebar"), &mut state.no_titlebar);
             ui.same_line(150.0);
             ui.checkbox(im_str!("No scrollbar"), &mut state.no_scrollbar);
@@ -386,14 +386,14 @@ fn show_test_window(ui: &Ui, state: &mut State, opened: &mut bool) {
             ui.checkbox(im_str!("No collapse"), &mut state.no_collapse);
             ui.checkbox(im_str!("No close"), &mut state.no_close);
 
-            TreeNode::new(im_str!("Style")).build(&ui, || {
+            TreeNode::new(im_str!("Style")).build(ui, || {
                 ui.show_default_style_editor();
             });
         }
-        if CollapsingHeader::new(im_str!("Widgets")).build(&ui) {
-            TreeNode::new(im_str!("Tree")).build(&ui, || {
+        if CollapsingHeader::new(im_str!("Widgets")).build(ui) {
+            TreeNode::new(im_str!("Tree")).build(ui, || {
                 for i in 0..5 {
-                    TreeNode::new(&im_str!("Child {}", i)).build(&ui, || {
+                    TreeNode::new(&im_str!("Child {}", i)).build(ui, || {
                         ui.text(im_str!("blah blah"));
                         ui.same_line(0.0);
                         if ui.small_button(im_str!("print")) {
@@ -403,7 +403,7 @@ fn show_test_window(ui: &Ui, state: &mut State, opened: &mut bool) {
                 }
             });
 
-            TreeNode::new(im_str!("Bullets")).build(&ui, || {
+            TreeNode::new(im_str!("Bullets")).build(ui, || {
                 ui.bullet_text(im_str!("Bullet point 1"));
                 ui.bullet_text(im_str!("Bullet point 2\nOn multiple lines"));
                 ui.bullet();
@@ -412,13 +412,13 @@ fn show_test_window(ui: &Ui, state: &mut State, opened: &mut bool) {
                 ui.bullet();
                 ui.small_button(im_str!("Button"));
             });
-            TreeNode::new(im_str!("Colored text")).build(&ui, || {
+            TreeNode::new(im_str!("Colored text")).build(ui, || {
                 ui.text_colored([1.0, 0.0, 1.0, 1.0], im_str!("Pink"));
                 ui.text_colored([1.0, 1.0, 0.0, 1.0], im_str!("Yellow"));
                 ui.text_disabled(im_str!("Disabled"));
             });
 
-            TreeNode::new(im_str!("Multi-line text")).build(&ui, || {
+            TreeNode::new(im_str!("Multi-line text")).build(ui, || {
                 ui.input_text_multiline(
                     im_str!("multiline"),
                     &mut state.text_multiline,
@@ -426,7 +426,7 @@ fn show_test_window(ui: &Ui, state: &mut State, opened: &mut bool) {
                 ).build();
             });
 
-            TreeNode::new(im_str!("Word wrapping")).build(&ui, || {
+            TreeNode::new(im_str!("Word wrapping")).build(ui, || {
                 ui.text_wrapped(im_str!(
                     "This text should automatically wrap on the edge of \
                      the window.The current implementation for text \
@@ -445,7 +445,7 @@ fn show_test_window(ui: &Ui, state: &mut State, opened: &mut bool) {
                 ui.text(im_str!("Test paragraph 2:"));
                 // TODO
             });
-            TreeNode::new(im_str!("UTF-8 Text")).build(&ui, || {
+            TreeNode::new(im_str!("UTF-8 Text")).build(ui, || {
                 ui.text_wrapped(im_str!(
                     "CJK text will only appear if the font was loaded \
                      with theappropriate CJK character ranges. Call \
@@ -504,7 +504,7 @@ fn show_test_window(ui: &Ui, state: &mut State, opened: &mut bool) {
             ColorEdit::new(im_str!("color 1"), &mut state.col1).build(ui);
             ColorEdit::new(im_str!("color 2"), &mut state.col2).build(ui);
 
-            TreeNode::new(im_str!("Multi-component Widgets")).build(&ui, || {
+            TreeNode::new(im_str!("Multi-component Widgets")).build(ui, || {
                 ui.input_float2(im_str!("input float2"), &mut state.vec2f)
                     .build();
                 ui.input_int2(im_str!("input int2"), &mut state.vec2i)
@@ -518,7 +518,7 @@ fn show_test_window(ui: &Ui, state: &mut State, opened: &mut bool) {
                 ui.spacing();
             });
 
-            TreeNode::new(im_str!("Color/Picker Widgets")).build(&ui, || {
+            TreeNode::new(im_str!("Color/Picker Widgets")).build(ui, || {
                 let s = &mut state.color_edit;
                 ui.checkbox(im_str!("With HDR"), &mut s.hdr);
                 ui.same_line(0.0);
@@ -620,26 +620,26 @@ CTRL+click on individual component to input value.\n",
             });
         }
 
-        if CollapsingHeader::new(im_str!("Layout")).build(&ui) {
-            TreeNode::new(im_str!("Tabs")).build(&ui, || {
-                TreeNode::new(im_str!("Basic")).build(&ui, || {
-                    TabBar::new(im_str!("basictabbar")).build(&ui, || {
-                        TabItem::new(im_str!("Avocado")).build(&ui, || {
+        if CollapsingHeader::new(im_str!("Layout")).build(ui) {
+            TreeNode::new(im_str!("Tabs")).build(ui, || {
+                TreeNode::new(im_str!("Basic")).build(ui, || {
+                    TabBar::new(im_str!("basictabbar")).build(ui, || {
+                        TabItem::new(im_str!("Avocado")).build(ui, || {
                             ui.text(im_str!("This is the Avocado tab!"));
                             ui.text(im_str!("blah blah blah blah blah"));
                         });
-                        TabItem::new(im_str!("Broccoli")).build(&ui, || {
+                        TabItem::new(im_str!("Broccoli")).build(ui, || {
                             ui.text(im_str!("This is the Broccoli tab!"));
                             ui.text(im_str!("blah blah blah blah blah"));
                         });
-                        TabItem::new(im_str!("Cucumber")).build(&ui, || {
+                        TabItem::new(im_str!("Cucumber")).build(ui, || {
                             ui.text(im_str!("This is the Cucumber tab!"));
                             ui.text(im_str!("blah blah blah blah blah"));
                         });
                     });
 
                 });
-                TreeNode::new(im_str!("Advanced & Close button")).build(&ui, || {
+                TreeNode::new(im_str!("Advanced & Close button")).build(ui, || {
 
                     ui.separator();
                     let s = &mut state.tabs;
@@ -675,17 +675,17 @@ CTRL+click on individual component to input value.\n",
                         f
                     };
 
-                    TabBar::new(im_str!("tabbar")).flags(flags).build(&ui, || {
-                        TabItem::new(im_str!("Artichoke")).opened(&mut s.artichoke_tab).build(&ui, || {
+                    TabBar::new(im_str!("tabbar")).flags(flags).build(ui, || {
+                        TabItem::new(im_str!("Artichoke")).opened(&mut s.artichoke_tab).build(ui, || {
                             ui.text(im_str!("This is the Artichoke tab!"));
                         });
-                        TabItem::new(im_str!("Beetroot")).opened(&mut s.beetroot_tab).build(&ui, || {
+                        TabItem::new(im_str!("Beetroot")).opened(&mut s.beetroot_tab).build(ui, || {
                             ui.text(im_str!("This is the Beetroot tab!"));
                         });
-                        TabItem::new(im_str!("Celery")).opened(&mut s.celery_tab).build(&ui, || {
+                        TabItem::new(im_str!("Celery")).opened(&mut s.celery_tab).build(ui, || {
                             ui.text(im_str!("This is the Celery tab!"));
                         });
-                        TabItem::new(im_str!("Daikon")).opened(&mut s.daikon_tab).build(&ui, || {
+                        TabItem::new(im_str!("Daikon")).opened(&mut s.daikon_tab).build(ui, || {
                             ui.text(im_str!("This is the Daikon tab!"));
                         });
                     });
@@ -693,8 +693,8 @@ CTRL+click on individual component to input value.\n",
                 });
             });
         }
-        if CollapsingHeader::new(im_str!("Popups & Modal windows")).build(&ui) {
-            TreeNode::new(im_str!("Popups")).build(&ui, || {
+        if CollapsingHeader::new(im_str!("Popups & Modal windows")).build(ui) {
+            TreeNode::new(im_str!("Popups")).build(ui, || {
                 ui.text_wrapped(im_str!(
                     "When a popup is active, it inhibits interacting \
                      with windows that are behind the popup. Clicking \
@@ -726,7 +726,7 @@ CTRL+click on individual component to input value.\n",
                 });
             });
 
-            TreeNode::new(im_str!("Modals")).build(&ui, || {
+            TreeNode::new(im_str!("Modals")).build(ui, || {
                 ui.text_wrapped(im_str!(
                     "Modal windows are like popups but the user cannot close \
                      them by clicking outside the window."
@@ -870,7 +870,7 @@ fn show_example_menu_file<'a>(ui: &Ui<'a>, state: &mut FileMenuState) {
         }
         menu.end(ui);
     }
-    if let Some(_) = ui.begin_menu(im_str!("Disabled"), false) {
+    if ui.begin_menu(im_str!("Disabled"), false).is_some() {
         unreachable!();
     }
     MenuItem::new(im_str!("Checked")).selected(true).build(ui);
@@ -0,0 +1,238 @@
+use vulkano::buffer::{BufferUsage, CpuBufferPool};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer};
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::{ImageCreateFlags, ImageDimensions, ImageUsage, StorageImage};
+use vulkano::image::view::{ComponentMapping, ComponentSwizzle, ImageView};
+use vulkano::sampler::{MipmapMode, Sampler};
+use vulkano::sync::{self, GpuFuture};
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::{FontAtlasFormat, RendererError, RendererOptions, Texture};
+
+/// The font atlas texture. The atlas is kept on the CPU as well, so that rebuilding it only
+/// uploads the rows that changed. Uploads are recorded into the command buffer of the next
+/// frame instead of being submitted and waited for.
+pub(crate) struct FontTexture {
+    image : Arc<StorageImage>,
+    texture : Texture,
+    sampler : Arc<Sampler>,
+    format : FontAtlasFormat,
+    pixels : Vec<u8>,
+    dimensions : [u32; 2],
+    // rows of `pixels` that haven't been uploaded to `image` yet
+    dirty : Option<Range<u32>>,
+    // no upload into `image` was recorded yet, so no frame can be using it
+    fresh : bool,
+    staging_pool : CpuBufferPool<u8>,
+}
+
+impl FontTexture {
+    pub fn new(mut fonts : imgui::FontAtlasRefMut, device : Arc<Device>, queue : Arc<Queue>, options : &RendererOptions) -> Result<FontTexture, RendererError> {
+        let format = options.font_atlas;
+        let atlas = Self::build(&mut fonts, format);
+        let dimensions = [atlas.width, atlas.height];
+        let pixels = atlas.data.to_vec();
+
+        // the atlas has a single mipmap level, so the mipmap mode and level of detail don't matter
+        let sampler = Sampler::new(
+            device.clone(),
+            options.font_filter,
+            options.font_filter,
+            MipmapMode::Nearest,
+            options.font_address_mode,
+            options.font_address_mode,
+            options.font_address_mode,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )?;
+
+        let (image, texture) = Self::create_image(device.clone(), queue, dimensions, format, sampler.clone())?;
+
+        Ok(FontTexture {
+            image,
+            texture,
+            sampler,
+            format,
+            pixels,
+            dimensions,
+            dirty : Some(0 .. dimensions[1]),
+            fresh : true,
+            staging_pool : CpuBufferPool::new(device, BufferUsage::transfer_source()),
+        })
+    }
+
+    /// Rebuild the atlas and mark the rows that changed for upload. The image is only recreated
+    /// if the size of the atlas changed.
+    pub fn update(&mut self, mut fonts : imgui::FontAtlasRefMut, device : Arc<Device>, queue : Arc<Queue>) -> Result<(), RendererError> {
+        let atlas = Self::build(&mut fonts, self.format);
+        let dimensions = [atlas.width, atlas.height];
+
+        if dimensions != self.dimensions {
+            let (image, texture) = Self::create_image(device, queue, dimensions, self.format, self.sampler.clone())?;
+            self.image = image;
+            self.texture = texture;
+            self.dimensions = dimensions;
+            self.pixels = atlas.data.to_vec();
+            self.dirty = Some(0 .. dimensions[1]);
+            self.fresh = true;
+        } else {
+            let row = dimensions[0] as usize * Self::texel_size(self.format);
+            if let Some(changed) = changed_rows(&self.pixels, atlas.data, row) {
+                self.dirty = Some(match self.dirty.take() {
+                    Some(dirty) => dirty.start.min(changed.start) .. dirty.end.max(changed.end),
+                    None => changed,
+                });
+                self.pixels.copy_from_slice(atlas.data);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record the upload of the rows that changed since the last upload, if there are any.
+    /// Must be called outside of a render pass.
+    pub fn record_upload(&mut self, cmd_buf_builder : &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> Result<(), RendererError> {
+        let rows = match self.dirty.take() {
+            Some(rows) => rows,
+            None => { return Ok(()); }
+        };
+
+        self.fresh = false;
+
+        let row = self.dimensions[0] as usize * Self::texel_size(self.format);
+        let staging = self.staging_pool.chunk(
+            self.pixels[rows.start as usize * row .. rows.end as usize * row].iter().cloned()
+        )?;
+
+        cmd_buf_builder.copy_buffer_to_image_dimensions(
+            staging,
+            self.image.clone(),
+            [0, rows.start, 0],
+            [self.dimensions[0], rows.end - rows.start, 1],
+            0,
+            1,
+            0,
+        )?;
+
+        Ok(())
+    }
+
+    /// Upload the atlas into a new image with a command buffer of its own, if it changed since
+    /// the last upload, and draw with the new image from now on. The previous image is left
+    /// untouched, so frames that are still using it aren't disturbed.
+    pub fn submit_upload(&mut self, device : Arc<Device>, queue : Arc<Queue>) -> Result<Box<dyn GpuFuture>, RendererError> {
+        if self.dirty.is_none() {
+            return Ok(sync::now(device).boxed());
+        }
+
+        if !self.fresh {
+            let (image, texture) = Self::create_image(device.clone(), queue.clone(), self.dimensions, self.format, self.sampler.clone())?;
+            self.image = image;
+            self.texture = texture;
+            self.dirty = Some(0 .. self.dimensions[1]);
+        }
+
+        let mut cmd_buf_builder = AutoCommandBufferBuilder::primary(device.clone(), queue.family(), CommandBufferUsage::OneTimeSubmit)?;
+        self.record_upload(&mut cmd_buf_builder)?;
+
+        Ok(sync::now(device).then_execute(queue, cmd_buf_builder.build()?)?.boxed())
+    }
+
+    /// Whether an upload into the current image was recorded, so it can be sampled.
+    pub fn is_uploaded(&self) -> bool {
+        !self.fresh
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    fn build<'a>(fonts : &'a mut imgui::FontAtlasRefMut, format : FontAtlasFormat) -> imgui::FontAtlasTexture<'a> {
+        match format {
+            FontAtlasFormat::Rgba32 => fonts.build_rgba32_texture(),
+            FontAtlasFormat::Alpha8 => fonts.build_alpha8_texture(),
+        }
+    }
+
+    fn texel_size(format : FontAtlasFormat) -> usize {
+        match format {
+            FontAtlasFormat::Rgba32 => 4,
+            FontAtlasFormat::Alpha8 => 1,
+        }
+    }
+
+    fn create_image(device : Arc<Device>, queue : Arc<Queue>, dimensions : [u32; 2], format : FontAtlasFormat, sampler : Arc<Sampler>) -> Result<(Arc<StorageImage>, Texture), RendererError> {
+        let (vk_format, component_mapping) = match format {
+            FontAtlasFormat::Rgba32 => (Format::R8G8B8A8Srgb, ComponentMapping::default()),
+            // sample the coverage as white with alpha, so the shaders treat it like the RGBA atlas
+            FontAtlasFormat::Alpha8 => (Format::R8Unorm, ComponentMapping {
+                r : ComponentSwizzle::One,
+                g : ComponentSwizzle::One,
+                b : ComponentSwizzle::One,
+                a : ComponentSwizzle::Red,
+            }),
+        };
+
+        let image = StorageImage::with_usage(
+            device,
+            ImageDimensions::Dim2d {
+                width : dimensions[0],
+                height : dimensions[1],
+                array_layers : 1,
+            },
+            vk_format,
+            ImageUsage {
+                sampled : true,
+                transfer_destination : true,
+                ..ImageUsage::none()
+            },
+            ImageCreateFlags::none(),
+            Some(queue.family()),
+        )?;
+
+        let view = ImageView::start(image.clone())
+            .with_component_mapping(component_mapping)
+            .build()?;
+
+        Ok((image, Texture::new(view, sampler)))
+    }
+}
+
+/// The rows that differ between two atlases of the same size, each row being `row` bytes long.
+fn changed_rows(old : &[u8], new : &[u8], row : usize) -> Option<Range<u32>> {
+    let changed = |y : &usize| old[y * row .. (y + 1) * row] != new[y * row .. (y + 1) * row];
+    let height = old.len() / row;
+
+    let first = (0 .. height).find(changed)?;
+    let last = (0 .. height).rev().find(changed)?;
+    Some(first as u32 .. last as u32 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::changed_rows;
+
+    #[test]
+    fn unchanged_atlas_has_no_changed_rows() {
+        let atlas = vec![7u8; 4 * 8];
+        assert_eq!(changed_rows(&atlas, &atlas, 4), None);
+    }
+
+    #[test]
+    fn changed_rows_span_first_to_last_change() {
+        let old = vec![0u8; 4 * 8];
+        let mut new = old.clone();
+        new[2 * 4 + 1] = 255;
+        new[5 * 4 + 3] = 255;
+        assert_eq!(changed_rows(&old, &new, 4), Some(2 .. 6));
+
+        let mut new = old.clone();
+        new[7 * 4] = 1;
+        assert_eq!(changed_rows(&old, &new, 4), Some(7 .. 8));
+    }
+}
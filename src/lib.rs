@@ -1,26 +1,47 @@
 mod shader;
+mod font;
 
-use vulkano::{buffer::{BufferAccess, BufferUsage, CpuBufferPool}, command_buffer::{PrimaryAutoCommandBuffer, SubpassContents}, image::{ImageDimensions, ImageViewAbstract, view::ImageView}, render_pass::RenderPass};
-use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
-use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::{buffer::{BufferAccess, BufferUsage, CpuAccessibleBuffer, CpuBufferPool, DeviceLocalBuffer, TypedBufferAccess}, command_buffer::{PrimaryAutoCommandBuffer, SubpassContents}, image::{ImageDimensions, ImageViewAbstract, view::ImageView}, render_pass::RenderPass};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, DynamicState, PrimaryCommandBuffer};
+use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
 use vulkano::descriptor::PipelineLayoutAbstract;
 use vulkano::device::{Device, Queue};
 use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract};
-use vulkano::sync::GpuFuture;
+use vulkano::pipeline::blend::{AttachmentBlend, BlendFactor};
+use vulkano::sync::{FenceSignalFuture, GpuFuture};
 
-use vulkano::image::ImmutableImage;
-use vulkano::sampler::Sampler;
-// use vulkano::sampler::{Sampler, SamplerAddressMode, Filter, MipmapMode};
+use vulkano::image::{AttachmentImage, ImageAccess, ImageDescriptorLayouts, ImageInner, ImageUsage};
+use vulkano::sync::AccessError;
+use vulkano::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreationError};
 use vulkano::format::{Format, ClearValue};
-use vulkano::render_pass::Subpass;
-use vulkano::render_pass::Framebuffer;
+use vulkano::render_pass::{AttachmentDesc, LoadOp, RenderPassDesc, StoreOp, Subpass, SubpassDesc};
+use vulkano::image::ImageLayout;
+use vulkano::render_pass::{Framebuffer, FramebufferAbstract};
+use vulkano::device::DeviceOwned;
+use vulkano::VulkanObject;
 use vulkano::pipeline::viewport::Scissor;
 use vulkano::pipeline::viewport::Viewport;
+use vulkano::OomError;
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::render_pass::{FramebufferCreationError, RenderPassCreationError};
+use vulkano::pipeline::GraphicsPipelineCreationError;
+use vulkano::image::ImageCreationError;
+use vulkano::image::view::ImageViewCreationError;
+use vulkano::descriptor::descriptor_set::{PersistentDescriptorSetBuildError, PersistentDescriptorSetError};
+use vulkano::command_buffer::{AutoCommandBufferBuilderContextError, BeginRenderPassError, BuildError, ClearColorImageError, CommandBufferExecError, CopyBufferError, CopyBufferImageError, DrawIndexedError};
+use vulkano::buffer::cpu_access::ReadLockError;
+use vulkano::sync::FlushError;
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::collections::HashMap;
 
-use imgui::{DrawVert, Textures, DrawCmd, DrawCmdParams, internal::RawWrapper, TextureId, ImString};
+use font::FontTexture;
+
+use imgui::{DrawVert, Textures, DrawCmd, DrawCmdParams, internal::RawWrapper, TextureId, ImString, BackendFlags};
 
 #[derive(Default, Debug, Clone)]
 #[repr(C)]
@@ -33,43 +54,852 @@ struct Vertex {
 
 vulkano::impl_vertex!(Vertex, pos, uv, col);
 
+// The index type uploaded to the GPU, vulkano picks the matching Vulkan index type from it.
+// Converting from `imgui::DrawIdx` fails to compile if imgui was built with 32-bit indices
+// but the `idx32` feature is disabled, and the assertion below fails if it wasn't but the
+// feature is enabled, which would only widen the indices.
+#[cfg(not(feature = "idx32"))]
+type DrawIndex = u16;
+#[cfg(feature = "idx32")]
+type DrawIndex = u32;
+#[cfg(feature = "idx32")]
+const _ : () = assert!(std::mem::size_of::<imgui::DrawIdx>() == 4, "the idx32 feature needs imgui built with ImDrawIdx = u32");
+
 impl From<DrawVert> for Vertex {
     fn from(v : DrawVert) -> Vertex {
         unsafe{std::mem::transmute(v)}
     }
 }
 
+/// The errors returned by the renderer. Failures of vulkano calls are wrapped in the variant
+/// for the vulkano error type, so they can be matched on.
 #[derive(Debug)]
 pub enum RendererError {
     BadTexture(TextureId),
     BadImageDimensions(ImageDimensions),
+    /// A draw command's vertex offset is past the end of its draw list's vertex buffer.
+    BadVertexOffset(usize),
+    /// A draw command's indices are out of the bounds of its draw list's index buffer.
+    BadIndexRange(std::ops::Range<usize>),
+    /// The graphics pipeline has no descriptor set layout for the texture.
+    MissingDescriptorSetLayout,
+    /// Pixels can't be read back from images of this format, which must be 8-bit RGBA or BGRA.
+    UnsupportedFormat(Format),
+    /// `RendererOptions::samples` isn't a power of two supported for color attachments by the device.
+    UnsupportedSampleCount(u32),
+    /// `Capture::wait` was called before the captured frame was drawn, or with the fence of a
+    /// submission that doesn't contain it.
+    CaptureNotDrawn,
+    /// `AttachmentLoad::Load` was combined with multisampling. The multisampled image belongs to
+    /// the renderer, so there is nothing to load.
+    LoadWithMultisampling,
+    /// `draw_commands_in_subpass` was called before the font atlas was uploaded with
+    /// `Renderer::record_font_upload`.
+    FontAtlasNotUploaded,
+    /// A draw callback returned an error.
+    Callback(Box<dyn std::error::Error + Send + Sync>),
+    Oom(OomError),
+    DeviceMemoryAlloc(DeviceMemoryAllocError),
+    RenderPassCreation(RenderPassCreationError),
+    PipelineCreation(GraphicsPipelineCreationError),
+    FramebufferCreation(FramebufferCreationError),
+    ImageCreation(ImageCreationError),
+    ImageViewCreation(ImageViewCreationError),
+    SamplerCreation(SamplerCreationError),
+    DescriptorSet(PersistentDescriptorSetError),
+    DescriptorSetBuild(PersistentDescriptorSetBuildError),
+    BeginRenderPass(BeginRenderPassError),
+    DrawIndexed(DrawIndexedError),
+    CommandBuffer(AutoCommandBufferBuilderContextError),
+    ClearColorImage(ClearColorImageError),
+    CopyBuffer(CopyBufferError),
+    CopyBufferImage(CopyBufferImageError),
+    CommandBufferBuild(BuildError),
+    CommandBufferExec(CommandBufferExecError),
+    Flush(FlushError),
+    ReadLock(ReadLockError),
 }
 
 impl fmt::Display for RendererError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            &Self::BadTexture(ref t) => {
+            Self::BadTexture(t) => {
                 write!(f, "The Texture ID could not be found: {:?}", t)
             },
-            &Self::BadImageDimensions(d) => {
+            Self::BadImageDimensions(d) => {
                 write!(f, "Image Dimensions not supported (must be Dim2d): {:?}", d)
             },
+            Self::BadVertexOffset(o) => {
+                write!(f, "The vertex offset is out of bounds: {}", o)
+            },
+            Self::BadIndexRange(r) => {
+                write!(f, "The index range is out of bounds: {:?}", r)
+            },
+            Self::MissingDescriptorSetLayout => {
+                write!(f, "The graphics pipeline has no descriptor set layout")
+            },
+            Self::UnsupportedFormat(format) => {
+                write!(f, "Format not supported for reading pixels (must be 8-bit RGBA or BGRA): {:?}", format)
+            },
+            Self::UnsupportedSampleCount(samples) => {
+                write!(f, "The sample count is not supported by the device: {}", samples)
+            },
+            Self::CaptureNotDrawn => {
+                write!(f, "The captured frame was not drawn in the submission that was waited on")
+            },
+            Self::LoadWithMultisampling => {
+                write!(f, "The target can't be loaded when the UI is drawn multisampled")
+            },
+            Self::FontAtlasNotUploaded => {
+                write!(f, "The font atlas was not uploaded, record its upload with record_font_upload")
+            },
+            Self::Callback(e) => write!(f, "Draw callback failed: {}", e),
+            Self::Oom(e) => write!(f, "Out of memory: {}", e),
+            Self::DeviceMemoryAlloc(e) => write!(f, "Failed to allocate device memory: {}", e),
+            Self::RenderPassCreation(e) => write!(f, "Failed to create the render pass: {}", e),
+            Self::PipelineCreation(e) => write!(f, "Failed to create the graphics pipeline: {}", e),
+            Self::FramebufferCreation(e) => write!(f, "Failed to create the framebuffer: {}", e),
+            Self::ImageCreation(e) => write!(f, "Failed to create an image: {}", e),
+            Self::ImageViewCreation(e) => write!(f, "Failed to create an image view: {}", e),
+            Self::SamplerCreation(e) => write!(f, "Failed to create a sampler: {}", e),
+            Self::DescriptorSet(e) => write!(f, "Failed to add a texture to a descriptor set: {}", e),
+            Self::DescriptorSetBuild(e) => write!(f, "Failed to build a descriptor set: {}", e),
+            Self::BeginRenderPass(e) => write!(f, "Failed to begin the render pass: {}", e),
+            Self::DrawIndexed(e) => write!(f, "Failed to record a draw command: {}", e),
+            Self::CommandBuffer(e) => write!(f, "Failed to record a command: {}", e),
+            Self::ClearColorImage(e) => write!(f, "Failed to clear an image: {}", e),
+            Self::CopyBuffer(e) => write!(f, "Failed to copy a buffer: {}", e),
+            Self::CopyBufferImage(e) => write!(f, "Failed to copy an image to a buffer: {}", e),
+            Self::CommandBufferBuild(e) => write!(f, "Failed to build a command buffer: {}", e),
+            Self::CommandBufferExec(e) => write!(f, "Failed to execute a command buffer: {}", e),
+            Self::Flush(e) => write!(f, "Failed to submit or wait for an upload: {}", e),
+            Self::ReadLock(e) => write!(f, "Failed to read a buffer: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RendererError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::BadTexture(_) | Self::BadImageDimensions(_)
+                | Self::BadVertexOffset(_) | Self::BadIndexRange(_) | Self::MissingDescriptorSetLayout | Self::UnsupportedFormat(_)
+                | Self::UnsupportedSampleCount(_) | Self::CaptureNotDrawn | Self::LoadWithMultisampling
+                | Self::FontAtlasNotUploaded => None,
+            Self::Callback(e) => Some(e.as_ref()),
+            Self::Oom(e) => Some(e),
+            Self::DeviceMemoryAlloc(e) => Some(e),
+            Self::RenderPassCreation(e) => Some(e),
+            Self::PipelineCreation(e) => Some(e),
+            Self::FramebufferCreation(e) => Some(e),
+            Self::ImageCreation(e) => Some(e),
+            Self::ImageViewCreation(e) => Some(e),
+            Self::SamplerCreation(e) => Some(e),
+            Self::DescriptorSet(e) => Some(e),
+            Self::DescriptorSetBuild(e) => Some(e),
+            Self::BeginRenderPass(e) => Some(e),
+            Self::DrawIndexed(e) => Some(e),
+            Self::CommandBuffer(e) => Some(e),
+            Self::ClearColorImage(e) => Some(e),
+            Self::CopyBuffer(e) => Some(e),
+            Self::CopyBufferImage(e) => Some(e),
+            Self::CommandBufferBuild(e) => Some(e),
+            Self::CommandBufferExec(e) => Some(e),
+            Self::Flush(e) => Some(e),
+            Self::ReadLock(e) => Some(e),
+        }
+    }
+}
+
+macro_rules! impl_from_error {
+    ($($error:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$error> for RendererError {
+                fn from(e : $error) -> Self {
+                    Self::$variant(e)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_error! {
+    OomError => Oom,
+    DeviceMemoryAllocError => DeviceMemoryAlloc,
+    RenderPassCreationError => RenderPassCreation,
+    GraphicsPipelineCreationError => PipelineCreation,
+    FramebufferCreationError => FramebufferCreation,
+    ImageCreationError => ImageCreation,
+    ImageViewCreationError => ImageViewCreation,
+    SamplerCreationError => SamplerCreation,
+    PersistentDescriptorSetError => DescriptorSet,
+    PersistentDescriptorSetBuildError => DescriptorSetBuild,
+    BeginRenderPassError => BeginRenderPass,
+    DrawIndexedError => DrawIndexed,
+    AutoCommandBufferBuilderContextError => CommandBuffer,
+    ClearColorImageError => ClearColorImage,
+    CopyBufferError => CopyBuffer,
+    CopyBufferImageError => CopyBufferImage,
+    BuildError => CommandBufferBuild,
+    CommandBufferExecError => CommandBufferExec,
+    FlushError => Flush,
+    ReadLockError => ReadLock,
+}
+
+
+/// How the colors of a texture are blended with the target.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// The texture's colors are not multiplied by its alpha. This is what imgui itself draws.
+    Straight,
+    /// The texture's colors are already multiplied by its alpha.
+    Premultiplied,
+    /// The texture's alpha is ignored, so it covers what is behind it. The vertex color's alpha
+    /// still fades it.
+    Opaque,
+    /// The texture's colors, multiplied by its alpha, are added to the target.
+    Additive,
+}
+
+impl BlendMode {
+    const ALL : [BlendMode; 4] = [BlendMode::Straight, BlendMode::Premultiplied, BlendMode::Opaque, BlendMode::Additive];
+
+    /// The blend state for this mode. With `premultiplied_output`, the shader multiplies every
+    /// color by its alpha, so the source factors are one.
+    fn attachment_blend(self, premultiplied_output : bool) -> AttachmentBlend {
+        let premultiplied = premultiplied_output || self == BlendMode::Premultiplied;
+        let source = if premultiplied { BlendFactor::One } else { BlendFactor::SrcAlpha };
+
+        match self {
+            BlendMode::Straight | BlendMode::Premultiplied | BlendMode::Opaque => AttachmentBlend {
+                color_source : source,
+                alpha_source : source,
+                ..AttachmentBlend::alpha_blending()
+            },
+            // the target's alpha is left as it is
+            BlendMode::Additive => AttachmentBlend {
+                color_source : source,
+                color_destination : BlendFactor::One,
+                alpha_source : BlendFactor::Zero,
+                alpha_destination : BlendFactor::One,
+                ..AttachmentBlend::alpha_blending()
+            },
+        }
+    }
+
+    /// The value of the fragment shader's `ALPHA_MODE` specialization constant.
+    fn alpha_mode(self) -> i32 {
+        match self {
+            BlendMode::Straight | BlendMode::Additive => 0,
+            BlendMode::Premultiplied => 1,
+            BlendMode::Opaque => 2,
+        }
+    }
+}
+
+/// A texture in the renderer's registry: an image view, the sampler it is drawn with, and how
+/// its colors are blended.
+#[derive(Clone)]
+pub struct Texture {
+    pub view : Arc<dyn ImageViewAbstract + Send + Sync>,
+    pub sampler : Arc<Sampler>,
+    pub blend_mode : BlendMode,
+}
+
+impl Texture {
+    /// Create a texture with straight alpha.
+    pub fn new(view : Arc<dyn ImageViewAbstract + Send + Sync>, sampler : Arc<Sampler>) -> Texture {
+        Texture {
+            view,
+            sampler,
+            blend_mode : BlendMode::Straight,
+        }
+    }
+
+    /// Set how the texture's colors are blended.
+    pub fn with_blend_mode(mut self, blend_mode : BlendMode) -> Texture {
+        self.blend_mode = blend_mode;
+        self
+    }
+}
+
+impl From<(Arc<dyn ImageViewAbstract + Send + Sync>, Arc<Sampler>)> for Texture {
+    fn from((view, sampler) : (Arc<dyn ImageViewAbstract + Send + Sync>, Arc<Sampler>)) -> Texture {
+        Texture::new(view, sampler)
+    }
+}
+
+/// Identifies a draw callback registered with `Renderer::register_callback`. The ID of a
+/// removed callback may be handed out again.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CallbackId(usize);
+
+/// Where a draw callback is being drawn, passed to the callback when it runs.
+#[derive(Clone, Debug)]
+pub struct CallbackInfo {
+    /// The clip rectangle of the callback command in framebuffer pixels, as `[x0, y0, x1, y1]`.
+    pub clip_rect : [f32; 4],
+    /// The viewport the renderer draws the UI with.
+    pub viewport : Viewport,
+}
+
+/// A closure that records its own commands in the middle of the UI. It runs inside the
+/// renderer's render pass, so pipelines it uses must be built for `Renderer::render_pass`.
+pub type DrawCallback = Box<dyn FnMut(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, &CallbackInfo) -> Result<(), Box<dyn std::error::Error + Send + Sync>> + Send>;
+
+/// A draw callback along with the allocation whose address is its `CallbackId`. Callback
+/// commands added by `add_draw_callback` carry that address as their user data, which no
+/// other callback command can point at while the callback is registered.
+struct RegisteredCallback {
+    callback : DrawCallback,
+    _key : Box<u8>,
+}
+
+/// The function of callback commands added by `add_draw_callback`, which imgui needs to emit
+/// them. The renderer recognizes them by their user data instead, so this only runs for
+/// callbacks that were removed, and does nothing.
+unsafe extern "C" fn draw_callback_marker(_ : *const imgui::sys::ImDrawList, _ : *const imgui::sys::ImDrawCmd) {}
+
+/// Add a registered draw callback to the current window's draw list. When the frame is drawn
+/// with `Renderer::draw_commands`, the callback runs after the geometry added before it.
+pub fn add_draw_callback(_ui : &imgui::Ui, callback : CallbackId) {
+    // holding a `Ui` guarantees a frame is in progress, so there is a current window
+    unsafe {
+        imgui::sys::ImDrawList_AddCallback(
+            imgui::sys::igGetWindowDrawList(),
+            Some(draw_callback_marker),
+            callback.0 as *mut std::os::raw::c_void,
+        );
+    }
+}
+
+/// Framebuffers that have not been drawn to for this many frames are dropped from the cache,
+/// which releases the images of a recreated swapchain.
+const FRAMEBUFFER_MAX_UNUSED_FRAMES : u64 = 8;
+
+/// Statistics of the framebuffer cache used by `Renderer::draw_commands`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FramebufferCacheStats {
+    /// The number of frames drawn with a cached framebuffer.
+    pub hits : u64,
+    /// The number of frames that had to create a new framebuffer.
+    pub misses : u64,
+    /// The number of framebuffers currently cached.
+    pub cached : usize,
+}
+
+/// Statistics of the descriptor sets the renderer keeps for each texture.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DescriptorSetCacheStats {
+    /// The number of draw commands that used a cached descriptor set.
+    pub hits : u64,
+    /// The number of draw commands that had to create a new descriptor set.
+    pub misses : u64,
+    /// The number of descriptor sets currently cached.
+    pub cached : usize,
+}
+
+type Pipeline = Arc<dyn GraphicsPipelineAbstract + Send + Sync>;
+
+struct CachedDescriptorSet {
+    // the texture the set was created for, compared with the registered texture on every use
+    texture : Texture,
+    set : Arc<dyn DescriptorSet + Send + Sync>,
+}
+
+/// Whether two textures use the same image view, sampler and blend mode.
+fn same_texture(a : &Texture, b : &Texture) -> bool {
+    Arc::as_ptr(&a.view) as *const () == Arc::as_ptr(&b.view) as *const ()
+        && Arc::ptr_eq(&a.sampler, &b.sampler)
+        && a.blend_mode == b.blend_mode
+}
+
+/// The vertices and indices of all draw lists of a frame, one draw list after the other.
+struct FrameGeometry {
+    vertices : Arc<dyn TypedBufferAccess<Content = [Vertex]> + Send + Sync>,
+    indices : Arc<dyn TypedBufferAccess<Content = [DrawIndex]> + Send + Sync>,
+}
+
+/// Geometry copied to device-local buffers by `Renderer::record_geometry_upload`, along with
+/// the vertices and indices it was copied from, so it's only drawn for the same draw data.
+struct UploadedGeometry {
+    geometry : FrameGeometry,
+    vertices : Vec<DrawVert>,
+    indices : Vec<imgui::DrawIdx>,
+}
+
+impl UploadedGeometry {
+    fn matches(&self, draw_data : &imgui::DrawData) -> bool {
+        draw_data.draw_lists().flat_map(|draw_list| draw_list.vtx_buffer().iter()).eq(self.vertices.iter())
+            && draw_data.draw_lists().flat_map(|draw_list| draw_list.idx_buffer().iter()).eq(self.indices.iter())
+    }
+}
+
+/// An iterator that yields `len` items, for writing the draw lists of a frame to a
+/// `CpuBufferPool` chunk without collecting them first.
+struct ExactLen<I> {
+    iter : I,
+    len : usize,
+}
+
+impl<I : Iterator> Iterator for ExactLen<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let item = self.iter.next()?;
+        self.len -= 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<I : Iterator> ExactSizeIterator for ExactLen<I> {}
+
+/// Get a device-local buffer from `buffers` with room for `len` elements that no command buffer
+/// uses anymore. If there is none, a free buffer is replaced with one twice as large, or a new
+/// buffer is added when all of them are in use.
+fn reusable_buffer<T>(buffers : &mut Vec<Arc<DeviceLocalBuffer<[T]>>>, device : &Arc<Device>, len : usize, usage : BufferUsage) -> Result<Arc<DeviceLocalBuffer<[T]>>, RendererError>
+where T: Send + Sync + 'static {
+    // command buffers hold on to the buffers they use until the GPU finished executing them,
+    // so a buffer only the renderer refers to is free
+    let free = |buffer : &Arc<DeviceLocalBuffer<[T]>>| Arc::strong_count(buffer) == 1;
+
+    if let Some(buffer) = buffers.iter().find(|&b| free(b) && b.len() >= len) {
+        return Ok(buffer.clone());
+    }
+
+    let replaced = buffers.iter().position(free);
+    let capacity = match replaced {
+        Some(i) => (buffers[i].len() * 2).max(len),
+        None => len,
+    }.next_power_of_two();
+
+    let buffer = DeviceLocalBuffer::array(device.clone(), capacity, usage, std::iter::empty())?;
+    match replaced {
+        Some(i) => buffers[i] = buffer.clone(),
+        None => buffers.push(buffer.clone()),
+    }
+    Ok(buffer)
+}
+
+struct CachedFramebuffer {
+    framebuffer : Arc<dyn FramebufferAbstract + Send + Sync>,
+    last_used : u64,
+}
+
+/// Whether the hardware encodes colors written to an image of `format` as sRGB.
+fn is_srgb(format : Format) -> bool {
+    matches!(format,
+        Format::R8Srgb | Format::R8G8Srgb | Format::R8G8B8Srgb | Format::B8G8R8Srgb
+            | Format::R8G8B8A8Srgb | Format::B8G8R8A8Srgb | Format::A8B8G8R8SrgbPack32
+    )
+}
+
+/// Convert the bytes of an 8-bit RGBA or BGRA image to RGBA8. The bytes of sRGB and UNORM
+/// images are both displayed as sRGB values, so they are kept as they are.
+fn rgba8_pixels(format : Format, mut pixels : Vec<u8>) -> Result<Vec<u8>, RendererError> {
+    match format {
+        Format::R8G8B8A8Unorm | Format::R8G8B8A8Srgb => {},
+        Format::B8G8R8A8Unorm | Format::B8G8R8A8Srgb => {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        },
+        f => { return Err(RendererError::UnsupportedFormat(f)); }
+    }
+    Ok(pixels)
+}
+
+/// The pixels of a frame captured with `Renderer::request_capture`.
+#[derive(Clone, Debug)]
+pub struct CapturedImage {
+    /// The RGBA8 pixels of the frame, row by row without padding, with the same values as were
+    /// displayed. BGRA targets are converted to RGBA.
+    pub pixels : Vec<u8>,
+    /// The width and height of the frame.
+    pub dimensions : [u32; 2],
+    /// The format of the target the frame was drawn into.
+    pub format : Format,
+}
+
+/// The buffer a captured frame is copied into.
+#[derive(Clone)]
+struct CaptureCopy {
+    buffer : Arc<CpuAccessibleBuffer<[u8]>>,
+    dimensions : [u32; 2],
+    format : Format,
+}
+
+#[derive(Default)]
+struct CaptureState {
+    copy : Option<CaptureCopy>,
+    error : Option<RendererError>,
+    // set once a later frame is drawn, by which time the command buffer with the copy has been
+    // submitted. From then on the buffer being readable means the copy has finished.
+    submitted : bool,
+    waker : Option<Waker>,
+}
+
+/// A frame capture requested with `Renderer::request_capture`. Get the frame with `wait`, or
+/// through `try_get` or as a `Future` once the frame has been copied.
+pub struct Capture {
+    state : Arc<Mutex<CaptureState>>,
+}
+
+impl Capture {
+    /// Block until the frame has been copied and return it. `submission` is the fence of the
+    /// submission that executes the command buffer the frame was drawn into. Waiting on it
+    /// releases the buffer the frame was copied into, so no later frame needs to be drawn.
+    pub fn wait<F>(self, submission : &FenceSignalFuture<F>) -> Result<CapturedImage, RendererError>
+    where F: GpuFuture {
+        submission.wait(None)?;
+
+        {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            if state.copy.is_none() && state.error.is_none() {
+                return Err(RendererError::CaptureNotDrawn);
+            }
+            state.submitted = true;
+        }
+
+        // the copy is still locked if `submission` doesn't contain the frame
+        self.try_get().unwrap_or(Err(RendererError::CaptureNotDrawn))
+    }
+
+    /// Get the captured frame, or `None` if it hasn't been copied yet. The copy is done once
+    /// the command buffer with the captured frame finished executing and a later frame was drawn.
+    pub fn try_get(&self) -> Option<Result<CapturedImage, RendererError>> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(error) = state.error.take() {
+            return Some(Err(error));
+        }
+
+        let CaptureCopy { buffer, dimensions, format } = match state.copy {
+            Some(ref copy) if state.submitted => copy.clone(),
+            _ => { return None; }
+        };
+
+        let pixels = match buffer.read() {
+            Ok(pixels) => pixels.to_vec(),
+            Err(ReadLockError::GpuWriteLocked) => { return None; },
+            Err(e) => { return Some(Err(e.into())); },
+        };
+
+        Some(rgba8_pixels(format, pixels).map(|pixels| CapturedImage {
+            pixels,
+            dimensions,
+            format,
+        }))
+    }
+}
+
+impl Future for Capture {
+    type Output = Result<CapturedImage, RendererError>;
+
+    fn poll(self : Pin<&mut Self>, cx : &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(result) = self.try_get() {
+            return Poll::Ready(result);
+        }
+
+        // woken by the renderer when a frame is drawn, or when the capture fails
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// Gives access to the image of a target view that is shared with a framebuffer, so the image
+/// can be copied after the UI is drawn into it.
+struct TargetImage<I>(Arc<I>);
+
+unsafe impl<I> ImageAccess for TargetImage<I>
+where I: ImageViewAbstract {
+    fn inner(&self) -> ImageInner<'_> {
+        self.0.image().inner()
+    }
+
+    fn initial_layout_requirement(&self) -> ImageLayout {
+        self.0.image().initial_layout_requirement()
+    }
+
+    fn final_layout_requirement(&self) -> ImageLayout {
+        self.0.image().final_layout_requirement()
+    }
+
+    fn descriptor_layouts(&self) -> Option<ImageDescriptorLayouts> {
+        self.0.image().descriptor_layouts()
+    }
+
+    fn conflicts_buffer(&self, other : &dyn BufferAccess) -> bool {
+        self.0.image().conflicts_buffer(other)
+    }
+
+    fn conflicts_image(&self, other : &dyn ImageAccess) -> bool {
+        self.0.image().conflicts_image(other)
+    }
+
+    fn conflict_key(&self) -> u64 {
+        self.0.image().conflict_key()
+    }
+
+    fn current_miplevels_access(&self) -> std::ops::Range<u32> {
+        self.0.image().current_miplevels_access()
+    }
+
+    fn current_layer_levels_access(&self) -> std::ops::Range<u32> {
+        self.0.image().current_layer_levels_access()
+    }
+
+    fn try_gpu_lock(&self, exclusive_access : bool, expected_layout : ImageLayout) -> Result<(), AccessError> {
+        self.0.image().try_gpu_lock(exclusive_access, expected_layout)
+    }
+
+    unsafe fn increase_gpu_lock(&self) {
+        self.0.image().increase_gpu_lock()
+    }
+
+    unsafe fn unlock(&self, transitioned_layout : Option<ImageLayout>) {
+        self.0.image().unlock(transitioned_layout)
+    }
+
+    unsafe fn layout_initialized(&self) {
+        self.0.image().layout_initialized()
+    }
+
+    fn is_layout_initialized(&self) -> bool {
+        self.0.image().is_layout_initialized()
+    }
+
+    unsafe fn preinitialized_layout(&self) -> bool {
+        self.0.image().preinitialized_layout()
+    }
+}
+
+/// What the renderer's render pass does with the target before the UI is drawn.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AttachmentLoad {
+    /// Keep the target's contents and draw the UI over them.
+    Load,
+    /// Clear the target to the given RGBA color.
+    Clear([f32; 4]),
+    /// The target's contents are undefined, for when the UI covers the whole target.
+    DontCare,
+}
+
+/// How the font atlas is stored on the GPU.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FontAtlasFormat {
+    /// Four bytes per texel, as built by `FontAtlas::build_rgba32_texture`.
+    Rgba32,
+    /// One byte of coverage per texel in an `R8Unorm` image, as built by
+    /// `FontAtlas::build_alpha8_texture`. It is sampled as white with the coverage as alpha.
+    /// Colored glyphs added to the atlas lose their color.
+    Alpha8,
+}
+
+/// The paper white level recommended for HDR output by ITU-R BT.2408, in nits.
+pub const DEFAULT_PAPER_WHITE : f32 = 203.0;
+
+/// Where the vertices and indices of a frame are read from by the GPU.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GeometryUpload {
+    /// Host-visible buffers the vertices and indices are written to, which the GPU reads over
+    /// the bus while drawing.
+    HostVisible,
+    /// Device-local buffers the frame's geometry is copied into from host-visible staging
+    /// buffers before the render pass. This is faster on discrete GPUs when the UI is heavy.
+    DeviceLocal,
+}
+
+/// How colors are encoded for the target. The HDR outputs draw the UI's white at the
+/// `paper_white` level, in nits, and always treat vertex colors as sRGB values to linearize.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HdrOutput {
+    /// An SDR target, written as it is by every other option.
+    Sdr,
+    /// Linear BT.709 colors where 1.0 is 80 nits, for `R16G16B16A16Sfloat` targets with the
+    /// extended sRGB linear color space.
+    ScRgb { paper_white : f32 },
+    /// BT.2020 colors encoded with the PQ curve of HDR10, for targets such as
+    /// `A2B10G10R10UnormPack32` with the HDR10 ST 2084 color space. Blending happens on the
+    /// encoded colors, so translucent edges differ slightly from SDR.
+    Pq { paper_white : f32 },
+}
+
+impl HdrOutput {
+    /// The value of the fragment shader's `HDR_OUTPUT` and `HDR_SCALE` specialization constants.
+    fn shader_constants(self) -> (i32, f32) {
+        match self {
+            HdrOutput::Sdr => (0, 1.0),
+            HdrOutput::ScRgb { paper_white } => (1, paper_white / 80.0),
+            HdrOutput::Pq { paper_white } => (2, paper_white / 10000.0),
         }
     }
 }
 
-impl std::error::Error for RendererError {}
+/// Options for the renderer. `samples` and `load` configure the render pass created by
+/// `Renderer::init_with_options`, and are ignored by `Renderer::init_with_subpass`.
+#[derive(Clone, Debug)]
+pub struct RendererOptions {
+    /// The number of samples per pixel. With more than one, the UI is drawn into a multisampled
+    /// image owned by the renderer and resolved into the target, replacing its contents. To draw
+    /// over a multisampled scene, use `Renderer::init_with_subpass` with the scene's subpass instead.
+    pub samples : u32,
+    /// What happens to the target's contents at the start of the render pass. When multisampling,
+    /// this applies to the multisampled image, which can't be loaded, so `Load` is an error.
+    pub load : AttachmentLoad,
+    /// How the font atlas is stored. `Alpha8` uses a quarter of the memory of `Rgba32`, which
+    /// matters for atlases with large glyph ranges.
+    pub font_atlas : FontAtlasFormat,
+    /// The filter used when sampling the font atlas. `Filter::Nearest` keeps bitmap fonts crisp
+    /// when they are drawn at their native size.
+    pub font_filter : Filter,
+    /// How the font atlas is sampled outside of its edges. The default `ClampToEdge` keeps
+    /// glyphs at the edges of the atlas from bleeding into each other.
+    pub font_address_mode : SamplerAddressMode,
+    /// Write colors multiplied by their alpha and blend them as premultiplied, so the target can
+    /// be composited with premultiplied blending, or start out transparent, without dark fringes.
+    /// The alpha of the target then holds the UI's coverage. Works with every `BlendMode`.
+    pub premultiplied_alpha : bool,
+    /// Whether imgui's vertex colors, which are sRGB values, are converted to linear before they
+    /// are written. An sRGB target encodes what is written again, so converting makes the colors
+    /// match other imgui backends. `None` converts them when the target's format is sRGB, or
+    /// the output is HDR.
+    pub linearize_vertex_colors : Option<bool>,
+    /// How colors are encoded for HDR targets. The default `HdrOutput::Sdr` leaves them as they are.
+    pub hdr_output : HdrOutput,
+    /// Where the GPU reads the geometry of each frame from.
+    pub geometry_upload : GeometryUpload,
+}
 
+impl Default for RendererOptions {
+    fn default() -> Self {
+        RendererOptions {
+            samples : 1,
+            load : AttachmentLoad::Load,
+            font_atlas : FontAtlasFormat::Rgba32,
+            font_filter : Filter::Linear,
+            font_address_mode : SamplerAddressMode::ClampToEdge,
+            premultiplied_alpha : false,
+            linearize_vertex_colors : None,
+            hdr_output : HdrOutput::Sdr,
+            geometry_upload : GeometryUpload::HostVisible,
+        }
+    }
+}
+
+impl RendererOptions {
+    /// Check that the render pass these options describe can be created on `device`.
+    fn validate(&self, device : &Device) -> Result<(), RendererError> {
+        // the supported counts are a mask of `VkSampleCountFlagBits`, whose values are the counts
+        let supported = device.physical_device().limits().framebuffer_color_sample_counts();
+        if !self.samples.is_power_of_two() || supported & self.samples == 0 {
+            return Err(RendererError::UnsupportedSampleCount(self.samples));
+        }
 
-pub type Texture = (Arc<dyn ImageViewAbstract + Send + Sync>, Arc<Sampler>);
+        if self.samples > 1 && self.load == AttachmentLoad::Load {
+            return Err(RendererError::LoadWithMultisampling);
+        }
+
+        Ok(())
+    }
+
+    /// Describe the render pass these options create: a single subpass drawing into the target,
+    /// or into a multisampled attachment that is resolved into the target.
+    fn render_pass_desc(&self, format : Format) -> RenderPassDesc {
+        let attachment = |samples, load, store| AttachmentDesc {
+            format,
+            samples,
+            load,
+            store,
+            stencil_load : load,
+            stencil_store : store,
+            initial_layout : ImageLayout::ColorAttachmentOptimal,
+            final_layout : ImageLayout::ColorAttachmentOptimal,
+        };
+
+        let load = match self.load {
+            AttachmentLoad::Load => LoadOp::Load,
+            AttachmentLoad::Clear(_) => LoadOp::Clear,
+            AttachmentLoad::DontCare => LoadOp::DontCare,
+        };
+
+        if self.samples > 1 {
+            RenderPassDesc::new(
+                vec![
+                    attachment(self.samples, load, StoreOp::DontCare),
+                    attachment(1, LoadOp::DontCare, StoreOp::Store),
+                ],
+                vec![SubpassDesc {
+                    color_attachments : vec![(0, ImageLayout::ColorAttachmentOptimal)],
+                    depth_stencil : None,
+                    input_attachments : vec![],
+                    resolve_attachments : vec![(1, ImageLayout::ColorAttachmentOptimal)],
+                    preserve_attachments : vec![],
+                }],
+                vec![],
+            )
+        } else {
+            RenderPassDesc::new(
+                vec![attachment(1, load, StoreOp::Store)],
+                vec![SubpassDesc {
+                    color_attachments : vec![(0, ImageLayout::ColorAttachmentOptimal)],
+                    depth_stencil : None,
+                    input_attachments : vec![],
+                    resolve_attachments : vec![],
+                    preserve_attachments : vec![],
+                }],
+                vec![],
+            )
+        }
+    }
+
+    /// The clear values to begin the render pass with, one per attachment.
+    fn clear_values(&self, samples : u32) -> Vec<ClearValue> {
+        let clear = match self.load {
+            AttachmentLoad::Clear(color) => ClearValue::Float(color),
+            _ => ClearValue::None,
+        };
+
+        if samples > 1 {
+            vec![clear, ClearValue::None]
+        } else {
+            vec![clear]
+        }
+    }
+}
 
 pub struct Renderer {
-    render_pass : Arc<RenderPass>,
-    pipeline : Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
-    font_texture : Texture,
+    options : RendererOptions,
+    subpass : Subpass,
+    msaa_image : Option<Arc<ImageView<Arc<AttachmentImage>>>>,
+    offscreen_image : Option<Arc<AttachmentImage>>,
+    capture_requests : Vec<Arc<Mutex<CaptureState>>>,
+    recorded_captures : Vec<Arc<Mutex<CaptureState>>>,
+    // keyed by the target's Vulkan image handle and dimensions. The cached framebuffer keeps
+    // the image alive, so the handle can't be reused by another image while it is cached.
+    framebuffers : HashMap<(u64, [u32; 2]), CachedFramebuffer>,
+    frame : u64,
+    framebuffer_hits : u64,
+    framebuffer_misses : u64,
+    // one pipeline for each `BlendMode`
+    pipelines : HashMap<BlendMode, Pipeline>,
+    font_texture : FontTexture,
+    font_texture_id : TextureId,
     textures : Textures<Texture>,
+    descriptor_sets : HashMap<TextureId, CachedDescriptorSet>,
+    descriptor_set_hits : u64,
+    descriptor_set_misses : u64,
+    callbacks : HashMap<CallbackId, RegisteredCallback>,
     vrt_buffer_pool : CpuBufferPool<Vertex>,
-    idx_buffer_pool : CpuBufferPool<u16>,
+    idx_buffer_pool : CpuBufferPool<DrawIndex>,
+    // with `GeometryUpload::DeviceLocal`, the buffers the geometry of recent frames was copied into
+    device_vertex_buffers : Vec<Arc<DeviceLocalBuffer<[Vertex]>>>,
+    device_index_buffers : Vec<Arc<DeviceLocalBuffer<[DrawIndex]>>>,
+    // recorded by `record_geometry_upload` for the next `draw_commands_in_subpass`
+    uploaded_geometry : Option<UploadedGeometry>,
 }
 
 impl Renderer {
@@ -86,62 +916,225 @@ impl Renderer {
     /// `queue`: the Vulkano `Queue` object for the queue the font atlas texture will be created on.
     /// 
     /// `format`: the Vulkano `Format` that the render pass will use when storing the frame in the target image.
-    pub fn init(ctx: &mut imgui::Context, device : Arc<Device>, queue : Arc<Queue>, format : Format) -> Result<Renderer, Box<dyn std::error::Error>> {
-
-        let vs = shader::vs::Shader::load(device.clone()).unwrap();
-        let fs = shader::fs::Shader::load(device.clone()).unwrap();
-
-        let render_pass = Arc::new(
-            vulkano::single_pass_renderpass!(
-                device.clone(),
-                attachments: {
-                    color: {
-                        load: Load,
-                        store: Store,
-                        format: format,
-                        samples: 1,
-                    }
-                },
-                pass: {
-                    color: [color],
-                    depth_stencil: {}
-                }
-            )
-            .unwrap(),
-        );
+    pub fn init(ctx: &mut imgui::Context, device : Arc<Device>, queue : Arc<Queue>, format : Format) -> Result<Renderer, RendererError> {
+        Self::init_with_options(ctx, device, queue, format, RendererOptions::default())
+    }
+
+    /// Initialize the renderer object like `init`, configuring the render pass it creates.
+    /// 
+    /// ---
+    /// 
+    /// `ctx`: the ImGui `Context` object
+    /// 
+    /// `device`: the Vulkano `Device` object for the device you want to render the UI on.
+    /// 
+    /// `queue`: the Vulkano `Queue` object for the queue the font atlas texture will be created on.
+    /// 
+    /// `format`: the Vulkano `Format` that the render pass will use when storing the frame in the target image.
+    /// 
+    /// `options`: the `RendererOptions` for the render pass
+    pub fn init_with_options(ctx: &mut imgui::Context, device : Arc<Device>, queue : Arc<Queue>, format : Format, options : RendererOptions) -> Result<Renderer, RendererError> {
+
+        options.validate(&device)?;
 
-        let pipeline = Arc::new(GraphicsPipeline::start()
-            .vertex_input_single_buffer::<Vertex>()
-            .vertex_shader(vs.main_entry_point(), ())
-            .triangle_list()
-            .viewports_scissors_dynamic(1)
-            .fragment_shader(fs.main_entry_point(), ())
-            .blend_alpha_blending()
-            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
-            .build(device.clone())?);
+        let render_pass = Arc::new(RenderPass::new(device.clone(), options.render_pass_desc(format))?);
+
+        Self::init_with_subpass(ctx, device, queue, Subpass::from(render_pass, 0).unwrap(), options)
+    }
+
+    /// Initialize the renderer object to draw inside a subpass of an existing render pass, for
+    /// example as the last subpass of the main scene pass. Draw with `draw_commands_in_subpass`
+    /// while that subpass is active.
+    /// 
+    /// ---
+    /// 
+    /// `ctx`: the ImGui `Context` object
+    /// 
+    /// `device`: the Vulkano `Device` object for the device you want to render the UI on.
+    /// 
+    /// `queue`: the Vulkano `Queue` object for the queue the font atlas texture will be created on.
+    /// 
+    /// `subpass`: the subpass the UI will be drawn in. It must have one color attachment, which may
+    /// be multisampled.
+    /// 
+    /// `options`: the `RendererOptions`, of which the render pass options are ignored
+    pub fn init_with_subpass(ctx: &mut imgui::Context, device : Arc<Device>, queue : Arc<Queue>, subpass : Subpass, options : RendererOptions) -> Result<Renderer, RendererError> {
 
+        let pipelines = Self::create_pipelines(device.clone(), subpass.clone(), &options)?;
 
-        let textures = Textures::new();
+        let mut textures = Textures::new();
 
-        let font_texture = Self::upload_font_texture(ctx.fonts(), device.clone(), queue.clone())?;
+        let font_texture = FontTexture::new(ctx.fonts(), device.clone(), queue.clone(), &options)?;
+        let font_texture_id = textures.insert(font_texture.texture().clone());
+        ctx.fonts().tex_id = font_texture_id;
 
 
         ctx.set_renderer_name(Some(ImString::from(format!("imgui-vulkano-renderer {}", env!("CARGO_PKG_VERSION")))));
 
-        let vrt_buffer_pool = CpuBufferPool::new(device.clone(), BufferUsage::vertex_buffer_transfer_destination());
-        let idx_buffer_pool = CpuBufferPool::new(device.clone(), BufferUsage::index_buffer_transfer_destination());
+        // vertex offsets are applied by binding the vertex buffer at `vtx_offset`,
+        // so imgui can emit draw lists larger than the 16-bit index range
+        ctx.io_mut().backend_flags.insert(BackendFlags::RENDERER_HAS_VTX_OFFSET);
+
+        let (vrt_buffer_pool, idx_buffer_pool) = Self::create_buffer_pools(device.clone());
 
         Ok(Renderer {
-            render_pass,
-            pipeline : pipeline as Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+            options,
+            subpass,
+            msaa_image : None,
+            offscreen_image : None,
+            capture_requests : Vec::new(),
+            recorded_captures : Vec::new(),
+            framebuffers : HashMap::new(),
+            frame : 0,
+            framebuffer_hits : 0,
+            framebuffer_misses : 0,
+            pipelines,
             font_texture,
+            font_texture_id,
             textures,
+            descriptor_sets : HashMap::new(),
+            descriptor_set_hits : 0,
+            descriptor_set_misses : 0,
+            callbacks : HashMap::new(),
             vrt_buffer_pool,
             idx_buffer_pool,
+            device_vertex_buffers : Vec::new(),
+            device_index_buffers : Vec::new(),
+            uploaded_geometry : None,
         })
     }
 
-    /// Appends the draw commands for the UI frame to an `AutoCommandBufferBuilder`.
+    /// Recreate the renderer's resources on a new device, for example after the old one was lost.
+    /// The render pass is recreated from the description of the old one, along with the pipeline,
+    /// buffer pools and font atlas texture. Each of the given textures is passed to `reupload`
+    /// and replaced with the texture it returns, so the IDs held by imgui widgets stay valid.
+    /// Registered draw callbacks are kept, and must not use resources of the old device afterwards.
+    /// Everything is created before the renderer is changed, so if this returns an error the
+    /// renderer is left as it was.
+    /// 
+    /// ---
+    /// 
+    /// `ctx`: the ImGui `Context` object
+    /// 
+    /// `device`: the new Vulkano `Device` object to render the UI on
+    /// 
+    /// `queue`: the Vulkano `Queue` object for the queue the font atlas texture will be created on
+    /// 
+    /// `texture_ids`: the IDs of all textures registered in `Renderer::textures`, other than the
+    /// font atlas, which is recreated by the renderer. `imgui::Textures` can't be iterated over,
+    /// so textures that are left out keep their resources from the old device
+    /// 
+    /// `reupload`: called with the ID and old texture of each texture in `texture_ids`, returning
+    /// the same texture created on the new device
+    pub fn recreate_on_device<I, F>(&mut self, ctx : &mut imgui::Context, device : Arc<Device>, queue : Arc<Queue>, texture_ids : I, mut reupload : F) -> Result<(), RendererError>
+    where I: IntoIterator<Item = TextureId>, F: FnMut(TextureId, &Texture) -> Result<Texture, RendererError> {
+
+        let render_pass = Arc::new(RenderPass::new(device.clone(), self.subpass.render_pass().desc().clone())?);
+        let subpass = Subpass::from(render_pass, self.subpass.index()).unwrap();
+
+        let pipelines = Self::create_pipelines(device.clone(), subpass.clone(), &self.options)?;
+        let font_texture = FontTexture::new(ctx.fonts(), device.clone(), queue, &self.options)?;
+
+        let mut textures = Vec::new();
+        for id in texture_ids.into_iter().filter(|&id| id != self.font_texture_id) {
+            let texture = reupload(id, self.lookup_texture(id)?)?;
+            textures.push((id, texture));
+        }
+
+        let (vrt_buffer_pool, idx_buffer_pool) = Self::create_buffer_pools(device);
+
+        self.pipelines = pipelines;
+        self.subpass = subpass;
+        self.textures.replace(self.font_texture_id, font_texture.texture().clone());
+        self.font_texture = font_texture;
+        ctx.fonts().tex_id = self.font_texture_id;
+        for (id, texture) in textures {
+            self.textures.replace(id, texture);
+        }
+
+        self.vrt_buffer_pool = vrt_buffer_pool;
+        self.idx_buffer_pool = idx_buffer_pool;
+        self.device_vertex_buffers.clear();
+        self.device_index_buffers.clear();
+        self.uploaded_geometry = None;
+
+        self.descriptor_sets.clear();
+        self.invalidate_framebuffers();
+
+        Ok(())
+    }
+
+    /// Create the pools the geometry of each frame is written to. They are drawn from directly,
+    /// or used as staging buffers for `GeometryUpload::DeviceLocal`.
+    fn create_buffer_pools(device : Arc<Device>) -> (CpuBufferPool<Vertex>, CpuBufferPool<DrawIndex>) {
+        let usage = |usage : BufferUsage| BufferUsage {
+            transfer_source : true,
+            ..usage
+        };
+
+        (
+            CpuBufferPool::new(device.clone(), usage(BufferUsage::vertex_buffer())),
+            CpuBufferPool::new(device, usage(BufferUsage::index_buffer())),
+        )
+    }
+
+    fn create_pipelines(device : Arc<Device>, subpass : Subpass, options : &RendererOptions) -> Result<HashMap<BlendMode, Pipeline>, RendererError> {
+        let vs = shader::vs::Shader::load(device.clone())?;
+        let fs = shader::fs::Shader::load(device.clone())?;
+
+        let vertex_constants = shader::vs::SpecializationConstants {
+            LINEARIZE_COLORS : Self::linearize_vertex_colors(&subpass, options) as u32,
+        };
+
+        let (hdr_output, hdr_scale) = options.hdr_output.shader_constants();
+
+        let mut pipelines = HashMap::new();
+        for &blend_mode in BlendMode::ALL.iter() {
+            let constants = shader::fs::SpecializationConstants {
+                ALPHA_MODE : blend_mode.alpha_mode(),
+                PREMULTIPLY_OUTPUT : options.premultiplied_alpha as u32,
+                HDR_OUTPUT : hdr_output,
+                HDR_SCALE : hdr_scale,
+            };
+
+            let pipeline : Pipeline = Arc::new(GraphicsPipeline::start()
+                .vertex_input_single_buffer::<Vertex>()
+                .vertex_shader(vs.main_entry_point(), vertex_constants)
+                .triangle_list()
+                .viewports_scissors_dynamic(1)
+                .fragment_shader(fs.main_entry_point(), constants)
+                .blend_collective(blend_mode.attachment_blend(options.premultiplied_alpha))
+                .render_pass(subpass.clone())
+                .build(device.clone())?);
+
+            pipelines.insert(blend_mode, pipeline);
+        }
+
+        Ok(pipelines)
+    }
+
+    /// Whether the vertex colors are converted to linear, following the options or else the
+    /// format of the subpass's color attachment. HDR outputs expect linear colors on any format.
+    fn linearize_vertex_colors(subpass : &Subpass, options : &RendererOptions) -> bool {
+        options.linearize_vertex_colors.unwrap_or_else(|| {
+            if options.hdr_output != HdrOutput::Sdr {
+                return true;
+            }
+
+            let desc = subpass.render_pass().desc();
+            match desc.subpasses()[subpass.index() as usize].color_attachments.first() {
+                Some(&(attachment, _)) => is_srgb(desc.attachments()[attachment].format),
+                None => false,
+            }
+        })
+    }
+
+    fn pipeline(&self, blend_mode : BlendMode) -> &Pipeline {
+        &self.pipelines[&blend_mode]
+    }
+
+    /// Appends the draw commands for the UI frame to an `AutoCommandBufferBuilder`, beginning
+    /// and ending the renderer's own render pass with `target` as its only attachment.
     /// 
     /// ---
     /// 
@@ -154,12 +1147,127 @@ impl Renderer {
     /// `target`: the target image to render to
     /// 
     /// `draw_data`: the ImGui `DrawData` that each UI frame creates
-    pub fn draw_commands<I>(&mut self, cmd_buf_builder : &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, _queue : Arc<Queue>, target : I, draw_data : &imgui::DrawData) -> Result<(), Box<dyn std::error::Error>> 
+    pub fn draw_commands<I>(&mut self, cmd_buf_builder : &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, _queue : Arc<Queue>, target : I, draw_data : &imgui::DrawData) -> Result<(), RendererError> 
+    where I: ImageViewAbstract + Send + Sync + 'static {
+
+        // captures recorded in an earlier frame have been submitted by now. Their tasks are woken
+        // on every frame to check whether the copy finished, until the `Capture` is dropped.
+        self.recorded_captures.retain(|capture| {
+            let mut state = capture.lock().unwrap_or_else(|e| e.into_inner());
+            state.submitted = true;
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+            Arc::strong_count(capture) > 1
+        });
+
+        let fb_width = draw_data.display_size[0] * draw_data.framebuffer_scale[0];
+        let fb_height = draw_data.display_size[1] * draw_data.framebuffer_scale[1];
+        if !(fb_width > 0.0 && fb_height > 0.0) {
+            return Ok(());
+        }
+
+        let target = Arc::new(target);
+
+        let dims = match target.image().dimensions() {
+            ImageDimensions::Dim2d {width, height, ..} => {[width, height]},
+            d => { return Err(RendererError::BadImageDimensions(d));}
+        };
+
+        let samples = self.subpass.num_samples().unwrap_or(1);
+
+        let framebuffer = self.framebuffer(target.clone(), dims, samples)?;
+
+        self.font_texture.record_upload(cmd_buf_builder)?;
+        self.record_geometry_upload(cmd_buf_builder, draw_data)?;
+
+        cmd_buf_builder.begin_render_pass(framebuffer, SubpassContents::Inline, self.options.clear_values(samples))?;
+
+        self.draw_commands_in_subpass(cmd_buf_builder, dims, draw_data)?;
+
+        cmd_buf_builder.end_render_pass()?;
+
+        if !self.capture_requests.is_empty() {
+            self.record_captures(cmd_buf_builder, target, dims)?;
+        }
+
+        Ok(())
+    }
+
+    /// Capture the next frame drawn with `draw_commands`. A copy of the target is added to the
+    /// command buffer after the UI. The returned `Capture` can be waited on with the fence of the
+    /// submission, or resolves as a `Future` once the command buffer finished executing and a
+    /// later frame was drawn. The target must have been
+    /// created with the `transfer_source` usage, and have an 8-bit RGBA or BGRA format.
+    pub fn request_capture(&mut self) -> Capture {
+        let state = Arc::new(Mutex::new(CaptureState::default()));
+        self.capture_requests.push(state.clone());
+        Capture { state }
+    }
+
+    fn record_captures<I>(&mut self, cmd_buf_builder : &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, target : Arc<I>, dims : [u32; 2]) -> Result<(), RendererError>
     where I: ImageViewAbstract + Send + Sync + 'static {
+        let format = target.format();
+
+        let copy = match format {
+            Format::R8G8B8A8Unorm | Format::R8G8B8A8Srgb | Format::B8G8R8A8Unorm | Format::B8G8R8A8Srgb => {
+                let buffer = CpuAccessibleBuffer::from_iter(
+                    self.subpass.render_pass().device().clone(),
+                    BufferUsage::transfer_destination(),
+                    false,
+                    (0 .. dims[0] * dims[1] * 4).map(|_| 0u8),
+                )?;
+                cmd_buf_builder.copy_image_to_buffer(TargetImage(target), buffer.clone())?;
+                Ok(CaptureCopy {
+                    buffer,
+                    dimensions : dims,
+                    format,
+                })
+            },
+            f => Err(f),
+        };
+
+        for capture in self.capture_requests.drain(..) {
+            let mut state = capture.lock().unwrap_or_else(|e| e.into_inner());
+            match copy {
+                Ok(ref copy) => {
+                    state.copy = Some(copy.clone());
+                    self.recorded_captures.push(capture.clone());
+                },
+                Err(f) => {
+                    state.error = Some(RendererError::UnsupportedFormat(f));
+                    if let Some(waker) = state.waker.take() {
+                        waker.wake();
+                    }
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Appends the draw commands for the UI frame to an `AutoCommandBufferBuilder` that is already
+    /// inside the renderer's subpass. Beginning and ending the render pass is left to the caller,
+    /// and so is recording the font atlas upload with `record_font_upload` before the render pass.
+    /// Returns `RendererError::FontAtlasNotUploaded` if the font atlas image was never uploaded.
+    /// 
+    /// ---
+    /// 
+    /// `cmd_buf_builder`: An `AutoCommandBufferBuilder` from vulkano, inside the subpass the renderer was created for
+    /// 
+    /// `dimensions`: the width and height of the framebuffer being rendered to
+    /// 
+    /// `draw_data`: the ImGui `DrawData` that each UI frame creates
+    pub fn draw_commands_in_subpass(&mut self, cmd_buf_builder : &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, dimensions : [u32; 2], draw_data : &imgui::DrawData) -> Result<(), RendererError> {
+
+        if !self.font_texture.is_uploaded() {
+            return Err(RendererError::FontAtlasNotUploaded);
+        }
 
         let fb_width = draw_data.display_size[0] * draw_data.framebuffer_scale[0];
         let fb_height = draw_data.display_size[1] * draw_data.framebuffer_scale[1];
         if !(fb_width > 0.0 && fb_height > 0.0) {
+            self.uploaded_geometry = None;
             return Ok(());
         }
         let left = draw_data.display_pos[0];
@@ -181,38 +1289,39 @@ impl Renderer {
             ]
         };
 
-        let dims = match target.image().dimensions() {
-            ImageDimensions::Dim2d {width, height, ..} => {[width, height]},
-            d => { return Err(Box::new(RendererError::BadImageDimensions(d)));}
-        };
-
-        let mut dynamic_state = DynamicState::default();
-        dynamic_state.viewports = Some(vec![
-            Viewport {
-                origin: [0.0, 0.0],
-                dimensions: [dims[0] as f32, dims[1] as f32],
-                depth_range: 0.0 .. 1.0,
-            }
-        ]);
-        dynamic_state.scissors = Some(vec![
-            Scissor::default()
-        ]);
+        let mut dynamic_state = Self::default_dynamic_state(dimensions);
 
         let clip_off = draw_data.display_pos;
         let clip_scale = draw_data.framebuffer_scale;
+        let to_framebuffer = |clip_rect : [f32; 4]| [
+            (clip_rect[0] - clip_off[0]) * clip_scale[0],
+            (clip_rect[1] - clip_off[1]) * clip_scale[1],
+            (clip_rect[2] - clip_off[0]) * clip_scale[0],
+            (clip_rect[3] - clip_off[1]) * clip_scale[1],
+        ];
 
-        
-        let layout = self.pipeline.descriptor_set_layout(0).unwrap();
+        // drop the descriptor sets of textures that were removed from the registry or replaced
+        let textures = &self.textures;
+        self.descriptor_sets.retain(|&id, cached| {
+            matches!(textures.get(id), Some(texture) if same_texture(texture, &cached.texture))
+        });
 
-        let framebuffer = Arc::new(Framebuffer::start(self.render_pass.clone())
-            .add(target)?.build()?);
+        // the uploaded geometry is only used if it was recorded for the same draw data
+        let geometry = match self.uploaded_geometry.take() {
+            Some(uploaded) if uploaded.matches(draw_data) => Some(uploaded.geometry),
+            _ => self.host_geometry(draw_data)?,
+        };
 
-        cmd_buf_builder.begin_render_pass(framebuffer, SubpassContents::Inline, vec![ClearValue::None])?;
+        let mut vtx_base = 0;
+        let mut idx_base = 0;
 
         for draw_list in draw_data.draw_lists() {
-            
-            let vertex_buffer = Arc::new(self.vrt_buffer_pool.chunk(draw_list.vtx_buffer().iter().map(|&v| Vertex::from(v))).unwrap());
-            let index_buffer  = Arc::new(self.idx_buffer_pool.chunk(draw_list.idx_buffer().iter().cloned()).unwrap());
+
+            // where the draw list is in the frame's geometry
+            let vertices = vtx_base .. vtx_base + draw_list.vtx_buffer().len();
+            let indices = idx_base .. idx_base + draw_list.idx_buffer().len();
+            vtx_base = vertices.end;
+            idx_base = indices.end;
 
             for cmd in draw_list.commands() {
                 match cmd {
@@ -222,17 +1331,11 @@ impl Renderer {
                             DrawCmdParams {
                                 clip_rect,
                                 texture_id,
-                                // vtx_offset,
+                                vtx_offset,
                                 idx_offset,
-                                ..
                             },
                     } => {
-                        let clip_rect = [
-                            (clip_rect[0] - clip_off[0]) * clip_scale[0],
-                            (clip_rect[1] - clip_off[1]) * clip_scale[1],
-                            (clip_rect[2] - clip_off[0]) * clip_scale[0],
-                            (clip_rect[3] - clip_off[1]) * clip_scale[1],
-                        ];
+                        let clip_rect = to_framebuffer(clip_rect);
 
                         if clip_rect[0] < fb_width
                             && clip_rect[1] < fb_height
@@ -253,36 +1356,218 @@ impl Renderer {
                                 };
                             }
 
-                            let tex = self.lookup_texture(texture_id)?;
+                            let (pipeline, set) = self.descriptor_set(texture_id)?;
+
+                            let bad_index_range = RendererError::BadIndexRange(idx_offset..(idx_offset+count));
+                            let geometry = match &geometry {
+                                Some(geometry) if idx_offset + count <= indices.len() => geometry,
+                                _ => { return Err(bad_index_range); }
+                            };
+                            if vtx_offset > vertices.len() {
+                                return Err(RendererError::BadVertexOffset(vtx_offset));
+                            }
 
-                            let set = Arc::new(PersistentDescriptorSet::start(layout.clone())
-                                .add_sampled_image(tex.0.clone(), tex.1.clone())?
-                                .build()?
+                            // Vulkano does not expose the base vertex of `vkCmdDrawIndexed`, so the
+                            // vertex buffer is bound starting at the command's vertex offset instead.
+                            let vertex_slice : Arc<dyn BufferAccess + Send + Sync> = Arc::new(
+                                geometry.vertices.clone().into_buffer_slice().slice(vertices.start + vtx_offset .. vertices.end)
+                                    .ok_or(RendererError::BadVertexOffset(vtx_offset))?
                             );
+                            let index_slice = geometry.indices.clone().into_buffer_slice().slice(indices.start + idx_offset .. indices.start + idx_offset + count)
+                                .ok_or(bad_index_range)?;
 
                             cmd_buf_builder.draw_indexed(
-                                self.pipeline.clone(), 
+                                pipeline,
                                 &dynamic_state, 
-                                vec![vertex_buffer.clone()], 
-                                index_buffer.clone().into_buffer_slice().slice(idx_offset..(idx_offset+count)).unwrap(),
+                                vec![vertex_slice], 
+                                index_slice,
                                 set,
                                 pc,
                                 vec![])?;
                         }
                     }
-                    DrawCmd::ResetRenderState => (), // TODO
-                    DrawCmd::RawCallback { callback, raw_cmd } => unsafe {
-                        callback(draw_list.raw(), raw_cmd)
+                    // Nothing to reset: the pipeline, descriptor set, push constants, viewport and
+                    // scissor are all handed to vulkano with every draw, which re-binds whatever a
+                    // callback changed.
+                    DrawCmd::ResetRenderState => (),
+                    DrawCmd::RawCallback { callback, raw_cmd } => {
+                        let (id, clip_rect) = unsafe {
+                            (CallbackId((*raw_cmd).UserCallbackData as usize), (*raw_cmd).ClipRect)
+                        };
+
+                        let registered = match self.callbacks.get_mut(&id) {
+                            Some(registered) => registered,
+                            None => {
+                                unsafe { callback(draw_list.raw(), raw_cmd) };
+                                continue;
+                            },
+                        };
+
+                        let info = CallbackInfo {
+                            clip_rect : to_framebuffer([clip_rect.x, clip_rect.y, clip_rect.z, clip_rect.w]),
+                            viewport : Self::viewport(dimensions),
+                        };
+
+                        // like for `DrawCmd::ResetRenderState`, the next draw re-binds the renderer's state
+                        (registered.callback)(cmd_buf_builder, &info).map_err(RendererError::Callback)?;
                     },
                 }
             }
         }
-        cmd_buf_builder.end_render_pass()?;
 
         Ok(())
     }
     
-    /// Update the ImGui font atlas texture.
+    /// Render a UI frame into an offscreen image and read its pixels back, for rendering without
+    /// a window. The image is cleared to transparent black before the UI is drawn, and is reused
+    /// by later calls with the same size and format. Blocks until the GPU is done.
+    /// 
+    /// ---
+    /// 
+    /// `queue`: the Vulkano `Queue` object for the queue the commands will be executed on
+    /// 
+    /// `dimensions`: the width and height of the image to render to
+    /// 
+    /// `format`: the format of the image, which must be the format the renderer was created for
+    /// and an 8-bit RGBA or BGRA format
+    /// 
+    /// `draw_data`: the ImGui `DrawData` that each UI frame creates
+    /// 
+    /// Returns the RGBA8 pixels of the image, row by row without padding.
+    pub fn render_offscreen(&mut self, queue : Arc<Queue>, dimensions : [u32; 2], format : Format, draw_data : &imgui::DrawData) -> Result<Vec<u8>, RendererError> {
+        // fail before drawing anything if the pixels can't be read back
+        rgba8_pixels(format, Vec::new())?;
+        let device = queue.device().clone();
+
+        let image = match self.offscreen_image {
+            Some(ref image) if image.dimensions().width_height() == dimensions && image.format() == format => image.clone(),
+            _ => {
+                let image = AttachmentImage::with_usage(
+                    device.clone(),
+                    dimensions,
+                    format,
+                    ImageUsage {
+                        transfer_source : true,
+                        transfer_destination : true,
+                        ..ImageUsage::color_attachment()
+                    },
+                )?;
+                self.offscreen_image = Some(image.clone());
+                image
+            }
+        };
+
+        let buffer = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::transfer_destination(),
+            false,
+            (0 .. dimensions[0] * dimensions[1] * 4).map(|_| 0u8),
+        )?;
+
+        let mut cmd_buf_builder = AutoCommandBufferBuilder::primary(device, queue.family(), CommandBufferUsage::OneTimeSubmit)?;
+
+        cmd_buf_builder.clear_color_image(image.clone(), ClearValue::Float([0.0; 4]))?;
+        self.draw_commands(&mut cmd_buf_builder, queue.clone(), ImageView::new(image.clone())?, draw_data)?;
+        cmd_buf_builder.copy_image_to_buffer(image, buffer.clone())?;
+
+        cmd_buf_builder.build()?
+            .execute(queue)?
+            .then_signal_fence_and_flush()?
+            .wait(None)?;
+
+        let pixels = buffer.read()?.to_vec();
+        rgba8_pixels(format, pixels)
+    }
+
+    /// Get the framebuffer for drawing into `target`, reusing the one created for the same image
+    /// and size in a previous frame if there is one.
+    fn framebuffer<I>(&mut self, target : I, dims : [u32; 2], samples : u32) -> Result<Arc<dyn FramebufferAbstract + Send + Sync>, RendererError>
+    where I: ImageViewAbstract + Send + Sync + 'static {
+        self.frame += 1;
+        let frame = self.frame;
+        self.framebuffers.retain(|_, cached| frame - cached.last_used <= FRAMEBUFFER_MAX_UNUSED_FRAMES);
+
+        let key = (target.image().inner().image.internal_object(), dims);
+        if let Some(cached) = self.framebuffers.get_mut(&key) {
+            cached.last_used = frame;
+            self.framebuffer_hits += 1;
+            return Ok(cached.framebuffer.clone());
+        }
+        self.framebuffer_misses += 1;
+
+        let framebuffer : Arc<dyn FramebufferAbstract + Send + Sync> = if samples > 1 {
+            let msaa_image = self.msaa_image(dims, target.format(), samples)?;
+            Arc::new(Framebuffer::start(self.subpass.render_pass().clone())
+                .add(msaa_image)?.add(target)?.build()?)
+        } else {
+            Arc::new(Framebuffer::start(self.subpass.render_pass().clone())
+                .add(target)?.build()?)
+        };
+
+        self.framebuffers.insert(key, CachedFramebuffer {
+            framebuffer : framebuffer.clone(),
+            last_used : frame,
+        });
+        Ok(framebuffer)
+    }
+
+    /// Drop all cached framebuffers, along with the references they hold to the target images.
+    /// Call this after recreating the swapchain to release the old images right away; otherwise
+    /// framebuffers are dropped once they have not been drawn to for a few frames.
+    pub fn invalidate_framebuffers(&mut self) {
+        self.framebuffers.clear();
+        self.msaa_image = None;
+        self.offscreen_image = None;
+    }
+
+    /// Get the hit and miss counts of the framebuffer cache used by `draw_commands`.
+    pub fn framebuffer_cache_stats(&self) -> FramebufferCacheStats {
+        FramebufferCacheStats {
+            hits : self.framebuffer_hits,
+            misses : self.framebuffer_misses,
+            cached : self.framebuffers.len(),
+        }
+    }
+
+    /// Get the multisampled image the UI is drawn into before being resolved into the target,
+    /// recreating it if the target's size or format changed.
+    fn msaa_image(&mut self, dims : [u32; 2], format : Format, samples : u32) -> Result<Arc<ImageView<Arc<AttachmentImage>>>, RendererError> {
+        if let Some(ref image) = self.msaa_image {
+            if image.image().dimensions().width_height() == dims && image.format() == format {
+                return Ok(image.clone());
+            }
+        }
+
+        let image = ImageView::new(AttachmentImage::transient_multisampled(self.subpass.render_pass().device().clone(), dims, samples, format)?)?;
+        self.msaa_image = Some(image.clone());
+        Ok(image)
+    }
+
+    fn viewport(dims : [u32; 2]) -> Viewport {
+        Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [dims[0] as f32, dims[1] as f32],
+            depth_range: 0.0 .. 1.0,
+        }
+    }
+
+    /// The dynamic state used at the start of a frame and after `DrawCmd::ResetRenderState`: a
+    /// viewport covering the whole target and a scissor that each draw command overwrites.
+    fn default_dynamic_state(dims : [u32; 2]) -> DynamicState {
+        DynamicState {
+            viewports : Some(vec![
+                Self::viewport(dims)
+            ]),
+            scissors : Some(vec![
+                Scissor::default()
+            ]),
+            ..DynamicState::none()
+        }
+    }
+
+    /// Update the ImGui font atlas texture. Only the rows of the atlas that changed are uploaded,
+    /// unless its size changed. The upload is recorded by the next `draw_commands` call, or by
+    /// `record_font_upload` when drawing in a subpass.
     /// 
     /// ---
     /// 
@@ -296,51 +1581,209 @@ impl Renderer {
         ctx: &mut imgui::Context,
         device : Arc<Device>,
         queue : Arc<Queue>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        self.font_texture = Self::upload_font_texture(ctx.fonts(), device, queue)?;
+    ) -> Result<(), RendererError> {
+        let previous = self.font_texture.texture().clone();
+        self.font_texture.update(ctx.fonts(), device, queue)?;
+        ctx.fonts().tex_id = self.font_texture_id;
+        self.register_font_texture(&previous);
+        Ok(())
+    }
+
+    /// Update the ImGui font atlas texture like `reload_font_texture`, but upload it right away
+    /// with `submit_font_upload` and return the future of the upload.
+    /// 
+    /// ---
+    /// 
+    /// `ctx`: the ImGui `Context` object
+    /// 
+    /// `device`: the Vulkano `Device` object for the device you want to render the UI on.
+    /// 
+    /// `queue`: the Vulkano `Queue` object for the queue the upload will be executed on.
+    pub fn reload_font_texture_with_future(
+        &mut self,
+        ctx: &mut imgui::Context,
+        device : Arc<Device>,
+        queue : Arc<Queue>,
+    ) -> Result<Box<dyn GpuFuture>, RendererError> {
+        self.reload_font_texture(ctx, device.clone(), queue.clone())?;
+        self.submit_font_upload(device, queue)
+    }
+
+    /// Submit the upload of the font atlas, if it changed since the last upload, instead of
+    /// recording it into the next frame. The atlas is uploaded into a new image and the previous
+    /// one is kept for frames that are still using it. The returned future must be joined with
+    /// the future of the next frame drawn, which will use the new atlas.
+    /// 
+    /// After `init`, this uploads the whole atlas.
+    pub fn submit_font_upload(&mut self, device : Arc<Device>, queue : Arc<Queue>) -> Result<Box<dyn GpuFuture>, RendererError> {
+        let previous = self.font_texture.texture().clone();
+        let future = self.font_texture.submit_upload(device, queue)?;
+        self.register_font_texture(&previous);
+        Ok(future)
+    }
+
+    /// Replace the font atlas in the texture registry if it was moved to a new image. Otherwise
+    /// the entry is left alone, in case it was replaced through `textures`.
+    fn register_font_texture(&mut self, previous : &Texture) {
+        if !same_texture(previous, self.font_texture.texture()) {
+            self.textures.replace(self.font_texture_id, self.font_texture.texture().clone());
+        }
+    }
+
+    /// Get the ID of the font atlas in the texture registry, for drawing it in custom widgets.
+    pub fn font_texture_id(&self) -> TextureId {
+        self.font_texture_id
+    }
+
+    /// Record the upload of the parts of the font atlas that changed since the last upload, if
+    /// there are any. `draw_commands` does this itself; when drawing with `draw_commands_in_subpass`,
+    /// call this every frame before beginning the render pass.
+    pub fn record_font_upload(&mut self, cmd_buf_builder : &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> Result<(), RendererError> {
+        self.font_texture.record_upload(cmd_buf_builder)
+    }
+
+    /// Record the copy of the frame's geometry into device-local buffers, if `RendererOptions::geometry_upload`
+    /// is `GeometryUpload::DeviceLocal`. `draw_commands` does this itself; when drawing with
+    /// `draw_commands_in_subpass`, call this with the same draw data before beginning the render
+    /// pass, or the frame is drawn from host-visible memory.
+    pub fn record_geometry_upload(&mut self, cmd_buf_builder : &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, draw_data : &imgui::DrawData) -> Result<(), RendererError> {
+        if self.options.geometry_upload != GeometryUpload::DeviceLocal {
+            return Ok(());
+        }
+        self.uploaded_geometry = None;
+
+        // kept to check that the geometry is drawn with the same draw data
+        let mut host_vertices = Vec::new();
+        let mut host_indices = Vec::new();
+        for draw_list in draw_data.draw_lists() {
+            host_vertices.extend_from_slice(draw_list.vtx_buffer());
+            host_indices.extend_from_slice(draw_list.idx_buffer());
+        }
+
+        // empty chunks can't be allocated
+        if host_vertices.is_empty() || host_indices.is_empty() {
+            return Ok(());
+        }
+
+        let staging_vertices = self.vrt_buffer_pool.chunk(host_vertices.iter().map(|&v| Vertex::from(v)))?;
+        let staging_indices = self.idx_buffer_pool.chunk(host_indices.iter().map(|&i| DrawIndex::from(i)))?;
+
+        let device = self.subpass.render_pass().device().clone();
+        let vertices = reusable_buffer(&mut self.device_vertex_buffers, &device, host_vertices.len(), BufferUsage::vertex_buffer_transfer_destination())?;
+        let indices = reusable_buffer(&mut self.device_index_buffers, &device, host_indices.len(), BufferUsage::index_buffer_transfer_destination())?;
+
+        // the device-local buffers may be larger, only the frame's geometry is copied
+        cmd_buf_builder
+            .copy_buffer(staging_vertices, vertices.clone())?
+            .copy_buffer(staging_indices, indices.clone())?;
+
+        self.uploaded_geometry = Some(UploadedGeometry {
+            geometry : FrameGeometry {
+                vertices,
+                indices,
+            },
+            vertices : host_vertices,
+            indices : host_indices,
+        });
         Ok(())
     }
 
+    /// Write the frame's geometry to host-visible buffers, or return `None` if it has none.
+    fn host_geometry(&self, draw_data : &imgui::DrawData) -> Result<Option<FrameGeometry>, RendererError> {
+        let vtx_count = draw_data.draw_lists().map(|draw_list| draw_list.vtx_buffer().len()).sum();
+        let idx_count = draw_data.draw_lists().map(|draw_list| draw_list.idx_buffer().len()).sum();
+
+        // empty chunks can't be allocated
+        if vtx_count == 0 || idx_count == 0 {
+            return Ok(None);
+        }
+
+        let vertices = ExactLen {
+            iter : draw_data.draw_lists().flat_map(|draw_list| draw_list.vtx_buffer().iter().map(|&v| Vertex::from(v))),
+            len : vtx_count,
+        };
+        let indices = ExactLen {
+            iter : draw_data.draw_lists().flat_map(|draw_list| draw_list.idx_buffer().iter().map(|&i| DrawIndex::from(i))),
+            len : idx_count,
+        };
+
+        Ok(Some(FrameGeometry {
+            vertices : Arc::new(self.vrt_buffer_pool.chunk(vertices)?),
+            indices : Arc::new(self.idx_buffer_pool.chunk(indices)?),
+        }))
+    }
+
     /// Get the texture library that the renderer uses
     pub fn textures(&mut self) -> &mut Textures<Texture> {
         &mut self.textures
     }
 
-    fn upload_font_texture(
-        mut fonts: imgui::FontAtlasRefMut,
-        device : Arc<Device>,
-        queue : Arc<Queue>,
-    ) -> Result<Texture, Box<dyn std::error::Error>> {
-        let texture = fonts.build_rgba32_texture();
-
-        let (image, fut) = ImmutableImage::from_iter(
-            texture.data.iter().cloned(),
-            ImageDimensions::Dim2d{
-                width : texture.width,
-                height : texture.height,
-                array_layers : 1,
-            },
-            vulkano::image::MipmapsCount::One,
-            Format::R8G8B8A8Srgb,
-            queue.clone(),
-            )?;
+    /// Get the render pass the UI is drawn in, for building pipelines used by draw callbacks.
+    pub fn render_pass(&self) -> Arc<RenderPass> {
+        self.subpass.render_pass().clone()
+    }
 
-        fut.then_signal_fence_and_flush()?
-            .wait(None)?;
+    /// Get the subpass the UI is drawn in.
+    pub fn subpass(&self) -> Subpass {
+        self.subpass.clone()
+    }
 
-        let sampler = Sampler::simple_repeat_linear(device.clone());
+    /// Register a closure that records commands in the middle of the UI. Add it to a window with
+    /// `add_draw_callback` using the returned ID. After it runs, the renderer restores its own state.
+    pub fn register_callback<F>(&mut self, callback : F) -> CallbackId
+    where F: FnMut(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, &CallbackInfo) -> Result<(), Box<dyn std::error::Error + Send + Sync>> + Send + 'static {
+        let key = Box::new(0u8);
+        let id = CallbackId(&*key as *const u8 as usize);
+        self.callbacks.insert(id, RegisteredCallback {
+            callback : Box::new(callback),
+            _key : key,
+        });
+        id
+    }
 
-        fonts.tex_id = TextureId::from(usize::MAX);
-        Ok((ImageView::new(image)?, sampler))
+    /// Remove a registered draw callback, returning it if it existed. Callback commands that
+    /// still use its ID do nothing.
+    pub fn remove_callback(&mut self, id : CallbackId) -> Option<DrawCallback> {
+        self.callbacks.remove(&id).map(|registered| registered.callback)
     }
 
-    fn lookup_texture(&self, texture_id: TextureId) -> Result<&Texture, RendererError> {
-        if texture_id.id() == usize::MAX {
-            Ok(&self.font_texture)
-        } else if let Some(texture) = self.textures.get(texture_id) {
-            Ok(texture)
-        } else {
-            Err(RendererError::BadTexture(texture_id))
+    /// Get the pipeline for a texture's blend mode and its descriptor set, creating the set if
+    /// the texture wasn't drawn before or was replaced since.
+    fn descriptor_set(&mut self, texture_id : TextureId) -> Result<(Pipeline, Arc<dyn DescriptorSet + Send + Sync>), RendererError> {
+        let texture = self.lookup_texture(texture_id)?.clone();
+        let pipeline = self.pipeline(texture.blend_mode).clone();
+
+        if let Some(cached) = self.descriptor_sets.get(&texture_id) {
+            if same_texture(&cached.texture, &texture) {
+                self.descriptor_set_hits += 1;
+                return Ok((pipeline, cached.set.clone()));
+            }
+        }
+        self.descriptor_set_misses += 1;
+
+        let layout = pipeline.descriptor_set_layout(0).ok_or(RendererError::MissingDescriptorSetLayout)?;
+        let set : Arc<dyn DescriptorSet + Send + Sync> = Arc::new(PersistentDescriptorSet::start(layout.clone())
+            .add_sampled_image(texture.view.clone(), texture.sampler.clone())?
+            .build()?
+        );
+
+        self.descriptor_sets.insert(texture_id, CachedDescriptorSet {
+            texture,
+            set : set.clone(),
+        });
+        Ok((pipeline, set))
+    }
+
+    /// Get the hit and miss counts of the descriptor sets reused across draw commands and frames.
+    pub fn descriptor_set_cache_stats(&self) -> DescriptorSetCacheStats {
+        DescriptorSetCacheStats {
+            hits : self.descriptor_set_hits,
+            misses : self.descriptor_set_misses,
+            cached : self.descriptor_sets.len(),
         }
     }
+
+    fn lookup_texture(&self, texture_id: TextureId) -> Result<&Texture, RendererError> {
+        self.textures.get(texture_id).ok_or(RendererError::BadTexture(texture_id))
+    }
 }
\ No newline at end of file
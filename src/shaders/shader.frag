@@ -1,5 +1,16 @@
 #version 450
 
+// how the texture's alpha is interpreted, one of the `BlendMode` variants:
+// 0: straight or additive, 1: premultiplied, 2: opaque
+layout(constant_id = 0) const int ALPHA_MODE = 0;
+// `RendererOptions::premultiplied_alpha`
+layout(constant_id = 1) const bool PREMULTIPLY_OUTPUT = false;
+// `RendererOptions::hdr_output`: 0: SDR, 1: scRGB, 2: PQ
+layout(constant_id = 2) const int HDR_OUTPUT = 0;
+// the paper white level relative to 1.0 of the HDR output, which is 80 nits for scRGB and
+// 10000 nits for PQ
+layout(constant_id = 3) const float HDR_SCALE = 1.0;
+
 layout(binding = 0) uniform sampler2D tex;
 
 layout(location = 0) in vec2 f_uv;
@@ -7,6 +18,52 @@ layout(location = 1) in vec4 f_color;
 
 layout(location = 0) out vec4 Target0;
 
+// BT.709 primaries to BT.2020 primaries, column by column
+const mat3 REC709_TO_REC2020 = mat3(
+    0.6274040, 0.0690970, 0.0163916,
+    0.3292820, 0.9195400, 0.0880132,
+    0.0433136, 0.0113612, 0.8955950
+);
+
+// SMPTE ST 2084, with 1.0 as 10000 nits
+vec3 pq_encode(vec3 color) {
+    const float m1 = 2610.0 / 16384.0;
+    const float m2 = 2523.0 / 4096.0 * 128.0;
+    const float c1 = 3424.0 / 4096.0;
+    const float c2 = 2413.0 / 4096.0 * 32.0;
+    const float c3 = 2392.0 / 4096.0 * 32.0;
+
+    vec3 y = pow(clamp(color, 0.0, 1.0), vec3(m1));
+    return pow((c1 + c2 * y) / (1.0 + c3 * y), vec3(m2));
+}
+
 void main() {
-    Target0 = f_color * texture(tex, f_uv.st);
+    vec4 tex_color = texture(tex, f_uv.st);
+    bool premultiplied = ALPHA_MODE == 1;
+
+    if (premultiplied) {
+        // the texture is already multiplied by its alpha, the vertex color isn't yet
+        Target0 = vec4(f_color.rgb * f_color.a, f_color.a) * tex_color;
+    } else if (ALPHA_MODE == 2) {
+        Target0 = f_color * vec4(tex_color.rgb, 1.0);
+    } else {
+        Target0 = f_color * tex_color;
+    }
+
+    if (HDR_OUTPUT == 1) {
+        Target0.rgb *= HDR_SCALE;
+    } else if (HDR_OUTPUT == 2) {
+        // PQ isn't linear, so it is applied to straight colors
+        if (premultiplied) {
+            Target0.rgb /= max(Target0.a, 1.0 / 65536.0);
+        }
+        Target0.rgb = pq_encode(REC709_TO_REC2020 * Target0.rgb * HDR_SCALE);
+        if (premultiplied) {
+            Target0.rgb *= Target0.a;
+        }
+    }
+
+    if (PREMULTIPLY_OUTPUT && !premultiplied) {
+        Target0.rgb *= Target0.a;
+    }
 }
\ No newline at end of file
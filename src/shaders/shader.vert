@@ -1,5 +1,8 @@
 #version 450
 
+// `RendererOptions::linearize_vertex_colors`
+layout(constant_id = 0) const bool LINEARIZE_COLORS = false;
+
 layout(push_constant) uniform VertPC {
     mat4 matrix;
 };
@@ -14,8 +17,17 @@ layout(location = 1) out vec4 f_color;
 // Built-in:
 // vec4 gl_Position
 
+vec4 srgb_to_linear(vec4 color) {
+    vec3 low = color.rgb / 12.92;
+    vec3 high = pow((color.rgb + 0.055) / 1.055, vec3(2.4));
+    return vec4(mix(high, low, lessThanEqual(color.rgb, vec3(0.04045))), color.a);
+}
+
 void main() {
     f_uv = uv;
     f_color = unpackUnorm4x8(col);
+    if (LINEARIZE_COLORS) {
+        f_color = srgb_to_linear(f_color);
+    }
     gl_Position = matrix * vec4(pos.xy, 0, 1);
 }
\ No newline at end of file
@@ -0,0 +1,45 @@
+mod common;
+
+use imgui_vulkano_renderer::{AttachmentLoad, BlendMode, RendererOptions};
+
+const SIZE : u32 = 64;
+
+#[test]
+fn textures_are_blended_by_their_mode() {
+    let options = RendererOptions {
+        load : AttachmentLoad::Clear([0.0, 0.0, 1.0, 1.0]),
+        ..RendererOptions::default()
+    };
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup_with_options(SIZE, common::FORMAT, options) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    // half transparent red in each mode, drawn over blue in four columns
+    let textures = [
+        ([255, 0, 0, 128], BlendMode::Straight),
+        ([128, 0, 0, 128], BlendMode::Premultiplied),
+        ([255, 0, 0, 0], BlendMode::Opaque),
+        ([255, 0, 0, 128], BlendMode::Additive),
+    ];
+    let texture_ids : Vec<_> = textures.iter().map(|&(color, blend_mode)| {
+        let texture = common::solid_texture(device.clone(), queue.clone(), color).with_blend_mode(blend_mode);
+        renderer.textures().insert(texture)
+    }).collect();
+
+    let ui = ctx.frame();
+    let column = SIZE as f32 / 4.0;
+    for (i, &texture_id) in texture_ids.iter().enumerate() {
+        common::add_image(&ui, texture_id, [i as f32 * column, 0.0], [(i + 1) as f32 * column, SIZE as f32]);
+    }
+    let draw_data = ui.render();
+
+    let pixels = common::render(&mut renderer, device, queue, [SIZE; 2], draw_data);
+
+    let expected = [[128, 0, 127], [128, 0, 127], [255, 0, 0], [128, 0, 255]];
+    for (i, expected) in expected.iter().enumerate() {
+        let actual = common::pixel(&pixels, SIZE, [i as u32 * SIZE / 4 + SIZE / 8, SIZE / 2]);
+        let close = actual.iter().zip(expected.iter()).all(|(&a, &e)| (a as i16 - e as i16).abs() <= 1);
+        assert!(close, "column {}: expected {:?}, got {:?}", i, expected, actual);
+    }
+}
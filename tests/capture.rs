@@ -0,0 +1,114 @@
+mod common;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Wake, Waker};
+
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryCommandBuffer};
+use vulkano::image::view::ImageView;
+use vulkano::sync::GpuFuture;
+
+const SIZE : u32 = 16;
+const RED : [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+#[test]
+fn capture_resolves_after_the_next_frame() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+    let image = common::target(device.clone(), [SIZE; 2]);
+
+    let capture = renderer.request_capture();
+
+    let ui = ctx.frame();
+    ui.get_background_draw_list()
+        .add_rect([0.0, 0.0], [SIZE as f32 / 2.0, SIZE as f32], RED)
+        .filled(true)
+        .build();
+    let draw_data = ui.render();
+    let drawn = common::render_into(&mut renderer, device.clone(), queue.clone(), image.clone(), draw_data);
+
+    assert!(capture.try_get().is_none());
+
+    // the next frame draws nothing, the capture still holds the first one
+    let ui = ctx.frame();
+    let draw_data = ui.render();
+    common::render_into(&mut renderer, device, queue, image, draw_data);
+
+    let captured = capture.try_get().unwrap().unwrap();
+    assert_eq!(captured.dimensions, [SIZE; 2]);
+    assert_eq!(captured.format, common::FORMAT);
+    assert_eq!(captured.pixels, drawn);
+    assert_eq!(common::pixel(&captured.pixels, SIZE, [SIZE / 4, SIZE / 2]), [255, 0, 0, 255]);
+}
+
+#[test]
+fn wait_resolves_without_another_frame() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+    let image = common::target(device.clone(), [SIZE; 2]);
+
+    let capture = renderer.request_capture();
+
+    let ui = ctx.frame();
+    ui.get_background_draw_list()
+        .add_rect([0.0, 0.0], [SIZE as f32 / 2.0, SIZE as f32], RED)
+        .filled(true)
+        .build();
+    let draw_data = ui.render();
+
+    let mut cmd_buf_builder = AutoCommandBufferBuilder::primary(device, queue.family(), CommandBufferUsage::OneTimeSubmit).unwrap();
+    cmd_buf_builder.clear_color_image(image.clone(), [0.0; 4].into()).unwrap();
+    renderer.draw_commands(&mut cmd_buf_builder, queue.clone(), ImageView::new(image).unwrap(), draw_data).unwrap();
+    let submission = cmd_buf_builder.build().unwrap()
+        .execute(queue).unwrap()
+        .then_signal_fence_and_flush().unwrap();
+
+    // the last frame, nothing is drawn after it
+    let captured = capture.wait(&submission).unwrap();
+    assert_eq!(common::pixel(&captured.pixels, SIZE, [SIZE / 4, SIZE / 2]), [255, 0, 0, 255]);
+    assert_eq!(common::pixel(&captured.pixels, SIZE, [3 * SIZE / 4, SIZE / 2]), [0, 0, 0, 0]);
+}
+
+/// Counts how often the task polling a capture is woken.
+struct CountingWaker(AtomicUsize);
+
+impl Wake for CountingWaker {
+    fn wake(self : Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn pending_capture_does_not_wake_itself() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+    let image = common::target(device.clone(), [SIZE; 2]);
+
+    let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(counter.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let mut capture = renderer.request_capture();
+    assert!(Pin::new(&mut capture).poll(&mut cx).is_pending());
+
+    let draw_data = ctx.frame().render();
+    common::render_into(&mut renderer, device.clone(), queue.clone(), image.clone(), draw_data);
+
+    // submitted and finished, but the renderer doesn't know yet
+    assert!(Pin::new(&mut capture).poll(&mut cx).is_pending());
+    assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+
+    let draw_data = ctx.frame().render();
+    common::render_into(&mut renderer, device, queue, image, draw_data);
+
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    assert!(Pin::new(&mut capture).poll(&mut cx).is_ready());
+}
@@ -0,0 +1,288 @@
+//! Helpers shared by the renderer integration tests.
+//!
+//! The tests render on whatever Vulkan implementation is available (a software one such as
+//! lavapipe is enough). When no device can be created they print a note and return early.
+
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, DynamicState, PrimaryAutoCommandBuffer, PrimaryCommandBuffer};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::device::{Device, DeviceExtensions, Features, Queue};
+use vulkano::format::Format;
+use vulkano::image::{AttachmentImage, ImageAccess, ImageDimensions, ImageUsage, ImmutableImage, MipmapsCount};
+use vulkano::image::view::ImageView;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::vertex::{BufferlessDefinition, BufferlessVertices};
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::render_pass::Subpass;
+use vulkano::sampler::Sampler;
+use vulkano::instance::{Instance, InstanceExtensions, PhysicalDevice, PhysicalDeviceType};
+use vulkano::sync::GpuFuture;
+
+use imgui_vulkano_renderer::{Renderer, RendererOptions, Texture};
+
+/// Format of the images the tests render into, so pixels can be compared as plain bytes.
+pub const FORMAT : Format = Format::R8G8B8A8Unorm;
+
+static IMGUI_LOCK : Mutex<()> = Mutex::new(());
+
+/// imgui only allows one active `Context` at a time, so tests creating one must hold this lock.
+pub fn lock_imgui() -> MutexGuard<'static, ()> {
+    IMGUI_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Create a device and graphics queue without a window, or `None` if Vulkan is unavailable.
+/// With `REQUIRE_VULKAN` set, a missing Vulkan device fails the test instead of skipping it.
+pub fn headless() -> Option<(Arc<Device>, Arc<Queue>)> {
+    let instance = instance()?;
+    let physical = match PhysicalDevice::enumerate(&instance).next() {
+        Some(physical) => physical,
+        None => { return unavailable("there is no Vulkan device".to_string()); }
+    };
+    create_device(physical)
+}
+
+/// Create a device and graphics queue like `headless`, but on a CPU implementation such as
+/// lavapipe, which the golden reference images are rendered with. Hardware devices round
+/// differently, so the test is skipped if there is no CPU device, even with `REQUIRE_VULKAN` set.
+pub fn software_headless() -> Option<(Arc<Device>, Arc<Queue>)> {
+    let instance = instance()?;
+    let physical = match PhysicalDevice::enumerate(&instance).find(|physical| physical.ty() == PhysicalDeviceType::Cpu) {
+        Some(physical) => physical,
+        None => {
+            eprintln!("skipping test, there is no CPU Vulkan device such as lavapipe");
+            return None;
+        },
+    };
+    create_device(physical)
+}
+
+fn instance() -> Option<Arc<Instance>> {
+    match Instance::new(None, &InstanceExtensions::none(), None) {
+        Ok(instance) => Some(instance),
+        Err(e) => unavailable(format!("Vulkan is not available: {}", e)),
+    }
+}
+
+fn create_device(physical : PhysicalDevice) -> Option<(Arc<Device>, Arc<Queue>)> {
+    let queue_family = match physical.queue_families().find(|q| q.supports_graphics()) {
+        Some(queue_family) => queue_family,
+        None => { return unavailable("the Vulkan device has no graphics queue".to_string()); }
+    };
+
+    match Device::new(
+        physical,
+        &Features::none(),
+        &DeviceExtensions::none(),
+        [(queue_family, 0.5)].iter().cloned(),
+    ) {
+        Ok((device, mut queues)) => Some((device, queues.next().unwrap())),
+        Err(e) => unavailable(format!("the Vulkan device could not be created: {}", e)),
+    }
+}
+
+/// Skip the test because of `reason`, or fail it if `REQUIRE_VULKAN` is set.
+fn unavailable<T>(reason : String) -> Option<T> {
+    if std::env::var_os("REQUIRE_VULKAN").is_some() {
+        panic!("Vulkan is required by REQUIRE_VULKAN, but {}", reason);
+    }
+    eprintln!("skipping test, {}", reason);
+    None
+}
+
+/// Create an imgui context with a fixed display size and no ini file.
+pub fn context(size : [f32; 2]) -> imgui::Context {
+    let mut ctx = imgui::Context::create();
+    ctx.set_ini_filename(None);
+    ctx.io_mut().display_size = size;
+    ctx
+}
+
+/// What `setup` returns: the imgui lock, the device and queue, a context and a renderer for it.
+pub type Setup = (MutexGuard<'static, ()>, Arc<Device>, Arc<Queue>, imgui::Context, Renderer);
+
+/// Create a device, a `size` by `size` context and a renderer drawing into `FORMAT`, or `None` if
+/// Vulkan is unavailable (see `headless`). Bind the returned lock first, so it is dropped last.
+pub fn setup(size : u32) -> Option<Setup> {
+    setup_with_options(size, FORMAT, RendererOptions::default())
+}
+
+/// Like `setup`, but the renderer draws into `format` and is configured by `options`.
+pub fn setup_with_options(size : u32, format : Format, options : RendererOptions) -> Option<Setup> {
+    let (device, queue) = headless()?;
+    let lock = lock_imgui();
+
+    let mut ctx = context([size as f32; 2]);
+    let renderer = Renderer::init_with_options(&mut ctx, device.clone(), queue.clone(), format, options).unwrap();
+    Some((lock, device, queue, ctx, renderer))
+}
+
+/// Create an image of `dims` the renderer can draw into and the tests can clear and read back.
+pub fn target(device : Arc<Device>, dims : [u32; 2]) -> Arc<AttachmentImage> {
+    target_with_format(device, dims, FORMAT)
+}
+
+/// Create a target image like `target` with another format.
+pub fn target_with_format(device : Arc<Device>, dims : [u32; 2], format : Format) -> Arc<AttachmentImage> {
+    AttachmentImage::with_usage(
+        device,
+        dims,
+        format,
+        ImageUsage {
+            transfer_source : true,
+            transfer_destination : true,
+            ..ImageUsage::color_attachment()
+        },
+    ).unwrap()
+}
+
+/// Render `draw_data` into a cleared image of `dims` and read the RGBA8 pixels back.
+pub fn render(renderer : &mut Renderer, device : Arc<Device>, queue : Arc<Queue>, dims : [u32; 2], draw_data : &imgui::DrawData) -> Vec<u8> {
+    let image = target(device.clone(), dims);
+    render_into(renderer, device, queue, image, draw_data)
+}
+
+/// Render `draw_data` into `image` after clearing it and read the bytes of its pixels back.
+pub fn render_into(renderer : &mut Renderer, device : Arc<Device>, queue : Arc<Queue>, image : Arc<AttachmentImage>, draw_data : &imgui::DrawData) -> Vec<u8> {
+    let dims = image.dimensions().width_height();
+    let texel_size = image.format().size().unwrap() as u32;
+
+    let buffer = CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        BufferUsage::transfer_destination(),
+        false,
+        (0 .. dims[0] * dims[1] * texel_size).map(|_| 0u8),
+    ).unwrap();
+
+    let mut cmd_buf_builder = AutoCommandBufferBuilder::primary(device, queue.family(), CommandBufferUsage::OneTimeSubmit).unwrap();
+
+    cmd_buf_builder.clear_color_image(image.clone(), [0.0; 4].into()).unwrap();
+    renderer.draw_commands(&mut cmd_buf_builder, queue.clone(), ImageView::new(image.clone()).unwrap(), draw_data).unwrap();
+    cmd_buf_builder.copy_image_to_buffer(image, buffer.clone()).unwrap();
+
+    cmd_buf_builder.build().unwrap()
+        .execute(queue).unwrap()
+        .then_signal_fence_and_flush().unwrap()
+        .wait(None).unwrap();
+
+    let pixels = buffer.read().unwrap().to_vec();
+    pixels
+}
+
+/// Create a 1x1 texture of a single RGBA8 color.
+pub fn solid_texture(device : Arc<Device>, queue : Arc<Queue>, color : [u8; 4]) -> Texture {
+    texture(device, queue, [1, 1], &color)
+}
+
+/// Create a texture of `dims` from RGBA8 pixels.
+pub fn texture(device : Arc<Device>, queue : Arc<Queue>, dims : [u32; 2], pixels : &[u8]) -> Texture {
+    let (image, fut) = ImmutableImage::from_iter(
+        pixels.iter().cloned(),
+        ImageDimensions::Dim2d { width : dims[0], height : dims[1], array_layers : 1 },
+        MipmapsCount::One,
+        FORMAT,
+        queue,
+    ).unwrap();
+    fut.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
+
+    Texture::new(ImageView::new(image).unwrap(), Sampler::simple_repeat_linear_no_mipmap(device))
+}
+
+/// Draw `texture` stretched over the rectangle from `min` to `max` on the background draw list.
+pub fn add_image(_ui : &imgui::Ui, texture : imgui::TextureId, min : [f32; 2], max : [f32; 2]) {
+    use imgui::sys::{ImVec2, igGetBackgroundDrawList, ImDrawList_AddImage};
+
+    unsafe {
+        ImDrawList_AddImage(
+            igGetBackgroundDrawList(),
+            texture.id() as *mut std::os::raw::c_void,
+            ImVec2::new(min[0], min[1]),
+            ImVec2::new(max[0], max[1]),
+            ImVec2::new(0.0, 0.0),
+            ImVec2::new(1.0, 1.0),
+            u32::MAX,
+        );
+    }
+}
+
+/// The RGBA8 value of pixel `[x, y]` in an image `width` pixels wide.
+pub fn pixel(pixels : &[u8], width : u32, [x, y] : [u32; 2]) -> [u8; 4] {
+    let i = ((y * width + x) * 4) as usize;
+    [pixels[i], pixels[i + 1], pixels[i + 2], pixels[i + 3]]
+}
+
+mod fill_vs {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        src: "
+#version 450
+
+// a triangle covering the whole viewport
+void main() {
+    vec2 corner = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    gl_Position = vec4(corner * 2.0 - 1.0, 0.0, 1.0);
+}"
+    }
+}
+
+mod fill_fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        src: "
+#version 450
+
+layout(push_constant) uniform FillPC {
+    vec4 color;
+};
+
+layout(location = 0) out vec4 Target0;
+
+void main() {
+    Target0 = color;
+}"
+    }
+}
+
+/// A pipeline for draw callbacks that fills its viewport with a color, without blending.
+pub type FillPipeline = Arc<GraphicsPipeline<BufferlessDefinition, Box<dyn PipelineLayoutAbstract + Send + Sync>>>;
+
+/// Create a `FillPipeline` for the renderer's subpass.
+pub fn fill_pipeline(device : Arc<Device>, subpass : Subpass) -> FillPipeline {
+    let vs = fill_vs::Shader::load(device.clone()).unwrap();
+    let fs = fill_fs::Shader::load(device.clone()).unwrap();
+
+    Arc::new(GraphicsPipeline::start()
+        .vertex_input(BufferlessDefinition {})
+        .vertex_shader(vs.main_entry_point(), ())
+        .triangle_list()
+        .viewports_dynamic_scissors_irrelevant(1)
+        .fragment_shader(fs.main_entry_point(), ())
+        .render_pass(subpass)
+        .build(device)
+        .unwrap())
+}
+
+/// Record a draw filling the rectangle from `min` to `max` with `color`, leaving the pipeline
+/// and viewport of the rectangle bound, as a callback drawing something of its own would.
+pub fn fill(cmd_buf_builder : &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, pipeline : &FillPipeline, min : [f32; 2], max : [f32; 2], color : [f32; 4]) {
+    let dynamic_state = DynamicState {
+        viewports : Some(vec![Viewport {
+            origin : min,
+            dimensions : [max[0] - min[0], max[1] - min[1]],
+            depth_range : 0.0 .. 1.0,
+        }]),
+        ..DynamicState::none()
+    };
+
+    cmd_buf_builder.draw(
+        pipeline.clone(),
+        &dynamic_state,
+        BufferlessVertices { vertices : 3, instances : 1 },
+        (),
+        fill_fs::ty::FillPC { color },
+        vec![],
+    ).unwrap();
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use imgui::{im_str, sys, Condition, Window, WindowFlags};
+use imgui_vulkano_renderer::{add_draw_callback, CallbackInfo};
+
+const SIZE : u32 = 64;
+const BLUE : [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+const RED : [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+#[test]
+fn callback_receives_clip_rect_and_viewport() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    let seen : Arc<Mutex<Vec<CallbackInfo>>> = Arc::new(Mutex::new(Vec::new()));
+    let callback = {
+        let seen = seen.clone();
+        renderer.register_callback(move |_, info| {
+            seen.lock().unwrap().push(info.clone());
+            Ok(())
+        })
+    };
+
+    let ui = ctx.frame();
+    Window::new(im_str!("preview"))
+        .position([0.0, 0.0], Condition::Always)
+        .size([SIZE as f32; 2], Condition::Always)
+        .build(&ui, || {
+            let draw_list = ui.get_window_draw_list();
+            draw_list.with_clip_rect([8.0, 8.0], [24.0, 24.0], || add_draw_callback(&ui, callback));
+        });
+    let draw_data = ui.render();
+
+    common::render(&mut renderer, device, queue, [SIZE; 2], draw_data);
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 1);
+    assert_eq!(seen[0].clip_rect, [8.0, 8.0, 24.0, 24.0]);
+    assert_eq!(seen[0].viewport.dimensions, [SIZE as f32; 2]);
+
+    assert!(renderer.remove_callback(callback).is_some());
+}
+
+#[test]
+fn state_is_restored_after_callback() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    // draws with its own pipeline and a viewport over the top left corner
+    let pipeline = common::fill_pipeline(device.clone(), renderer.subpass());
+    let callback = renderer.register_callback(move |cmd_buf_builder, _| {
+        common::fill(cmd_buf_builder, &pipeline, [0.0, 0.0], [8.0, 8.0], RED);
+        Ok(())
+    });
+
+    let ui = ctx.frame();
+    Window::new(im_str!("scene"))
+        .position([0.0, 0.0], Condition::Always)
+        .size([SIZE as f32; 2], Condition::Always)
+        .flags(WindowFlags::NO_DECORATION | WindowFlags::NO_BACKGROUND)
+        .build(&ui, || {
+            let draw_list = ui.get_window_draw_list();
+            add_draw_callback(&ui, callback);
+            // the lower half, so the callback's corner stays visible
+            draw_list.with_clip_rect([0.0, 0.0], [SIZE as f32; 2], || {
+                draw_list.add_rect([0.0, SIZE as f32 / 2.0], [SIZE as f32; 2], BLUE).filled(true).build();
+            });
+        });
+    let draw_data = ui.render();
+
+    let pixels = common::render(&mut renderer, device, queue, [SIZE; 2], draw_data);
+
+    for y in 0 .. SIZE {
+        for x in 0 .. SIZE {
+            let expected = if x < 8 && y < 8 {
+                [255, 0, 0, 255]
+            } else if y >= SIZE / 2 {
+                [0, 0, 255, 255]
+            } else {
+                [0, 0, 0, 0]
+            };
+            assert_eq!(common::pixel(&pixels, SIZE, [x, y]), expected, "pixel [{}, {}]", x, y);
+        }
+    }
+}
+
+static RAW_CALLS : AtomicUsize = AtomicUsize::new(0);
+
+unsafe extern "C" fn count_raw_calls(_ : *const sys::ImDrawList, cmd : *const sys::ImDrawCmd) {
+    RAW_CALLS.fetch_add((*cmd).UserCallbackData as usize, Ordering::SeqCst);
+}
+
+#[test]
+fn other_callbacks_are_left_to_imgui() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    let removed = renderer.register_callback(|_, _| Ok(()));
+    renderer.remove_callback(removed);
+
+    let ui = ctx.frame();
+    Window::new(im_str!("scene"))
+        .position([0.0, 0.0], Condition::Always)
+        .size([SIZE as f32; 2], Condition::Always)
+        .build(&ui, || {
+            // a raw callback is called with its own user data, a removed callback does nothing
+            unsafe {
+                sys::ImDrawList_AddCallback(sys::igGetWindowDrawList(), Some(count_raw_calls), 3 as *mut std::os::raw::c_void);
+            }
+            add_draw_callback(&ui, removed);
+        });
+    let draw_data = ui.render();
+
+    common::render(&mut renderer, device, queue, [SIZE; 2], draw_data);
+
+    assert_eq!(RAW_CALLS.load(Ordering::SeqCst), 3);
+}
@@ -0,0 +1,170 @@
+mod common;
+
+use imgui::{FontConfig, FontSource};
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
+use vulkano::image::view::ImageView;
+use vulkano::sync::GpuFuture;
+use imgui_vulkano_renderer::{FontAtlasFormat, Renderer, RendererOptions};
+
+const SIZE : u32 = 64;
+const GREEN : [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+
+#[test]
+fn fonts_added_at_runtime_are_drawn() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    // draw a frame with the original atlas, so its upload is done before the atlas changes
+    let ui = ctx.frame();
+    let draw_data = ui.render();
+    common::render(&mut renderer, device.clone(), queue.clone(), [SIZE; 2], draw_data);
+
+    let large = ctx.fonts().add_font(&[FontSource::DefaultFontData {
+        config : Some(FontConfig {
+            size_pixels : 40.0,
+            ..FontConfig::default()
+        }),
+    }]);
+    renderer.reload_font_texture(&mut ctx, device.clone(), queue.clone()).unwrap();
+
+    let ui = ctx.frame();
+    let font = ui.push_font(large);
+    ui.get_background_draw_list().add_text([0.0, 0.0], [1.0; 4], "W");
+    font.pop(&ui);
+    let draw_data = ui.render();
+
+    let pixels = common::render(&mut renderer, device, queue, [SIZE; 2], draw_data);
+
+    let lit = pixels.chunks_exact(4).filter(|p| p[0] > 128).count();
+    assert!(lit > 100, "only {} pixels of the glyph were drawn", lit);
+}
+
+#[test]
+fn fonts_added_without_growing_the_atlas_are_drawn() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    let ui = ctx.frame();
+    let draw_data = ui.render();
+    common::render(&mut renderer, device.clone(), queue.clone(), [SIZE; 2], draw_data);
+
+    // a small font fits in the free rows of the atlas, so only those are uploaded into the
+    // existing image
+    let dimensions = |ctx : &mut imgui::Context| {
+        let mut fonts = ctx.fonts();
+        let atlas = fonts.build_rgba32_texture();
+        [atlas.width, atlas.height]
+    };
+    let before = dimensions(&mut ctx);
+    let small = ctx.fonts().add_font(&[FontSource::DefaultFontData {
+        config : Some(FontConfig {
+            size_pixels : 8.0,
+            ..FontConfig::default()
+        }),
+    }]);
+    assert_eq!(dimensions(&mut ctx), before);
+    renderer.reload_font_texture(&mut ctx, device.clone(), queue.clone()).unwrap();
+
+    let ui = ctx.frame();
+    let font = ui.push_font(small);
+    ui.get_background_draw_list().add_text([0.0, 0.0], [1.0; 4], "WWWWWWWW");
+    font.pop(&ui);
+    let draw_data = ui.render();
+
+    let pixels = common::render(&mut renderer, device, queue, [SIZE; 2], draw_data);
+
+    let lit = pixels.chunks_exact(4).filter(|p| p[0] > 128).count();
+    assert!(lit > 20, "only {} pixels of the glyphs were drawn", lit);
+}
+
+#[test]
+fn alpha8_atlas_draws_white_glyphs_and_solid_fills() {
+    let (device, queue) = match common::headless() {
+        Some(dq) => dq,
+        None => return,
+    };
+    let _lock = common::lock_imgui();
+
+    let mut ctx = common::context([SIZE as f32; 2]);
+    ctx.fonts().add_font(&[FontSource::DefaultFontData {
+        config : Some(FontConfig {
+            size_pixels : 40.0,
+            ..FontConfig::default()
+        }),
+    }]);
+
+    let options = RendererOptions {
+        font_atlas : FontAtlasFormat::Alpha8,
+        ..RendererOptions::default()
+    };
+    let mut renderer = Renderer::init_with_options(&mut ctx, device.clone(), queue.clone(), common::FORMAT, options).unwrap();
+
+    let ui = ctx.frame();
+    {
+        let draw_list = ui.get_background_draw_list();
+        // solid fills sample the atlas' white texel, which must come out opaque white
+        draw_list.add_rect([0.0, SIZE as f32 / 2.0], [SIZE as f32; 2], GREEN).filled(true).build();
+        draw_list.add_text([0.0, -8.0], [1.0; 4], "W");
+    }
+    let draw_data = ui.render();
+
+    let pixels = common::render(&mut renderer, device, queue, [SIZE; 2], draw_data);
+
+    assert_eq!(common::pixel(&pixels, SIZE, [SIZE / 2, 3 * SIZE / 4]), [0, 255, 0, 255]);
+
+    let glyph = pixels.chunks_exact(4).take((SIZE * SIZE / 2) as usize)
+        .filter(|p| p[0] > 128 && p[0] == p[1] && p[1] == p[2])
+        .count();
+    assert!(glyph > 50, "only {} white pixels of the glyph were drawn", glyph);
+}
+
+#[test]
+fn submitted_uploads_are_joined_with_the_next_frame() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    renderer.submit_font_upload(device.clone(), queue.clone()).unwrap()
+        .then_signal_fence_and_flush().unwrap()
+        .wait(None).unwrap();
+
+    let ui = ctx.frame();
+    let draw_data = ui.render();
+    common::render(&mut renderer, device.clone(), queue.clone(), [SIZE; 2], draw_data);
+
+    let large = ctx.fonts().add_font(&[FontSource::DefaultFontData {
+        config : Some(FontConfig {
+            size_pixels : 40.0,
+            ..FontConfig::default()
+        }),
+    }]);
+    let upload = renderer.reload_font_texture_with_future(&mut ctx, device.clone(), queue.clone()).unwrap();
+
+    let ui = ctx.frame();
+    let font = ui.push_font(large);
+    ui.get_background_draw_list().add_text([0.0, 0.0], [1.0; 4], "W");
+    font.pop(&ui);
+    let draw_data = ui.render();
+
+    let image = common::target(device.clone(), [SIZE; 2]);
+    let buffer = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::transfer_destination(), false, (0 .. SIZE * SIZE * 4).map(|_| 0u8)).unwrap();
+
+    let mut cmd_buf_builder = AutoCommandBufferBuilder::primary(device, queue.family(), CommandBufferUsage::OneTimeSubmit).unwrap();
+    cmd_buf_builder.clear_color_image(image.clone(), [0.0; 4].into()).unwrap();
+    renderer.draw_commands(&mut cmd_buf_builder, queue.clone(), ImageView::new(image.clone()).unwrap(), draw_data).unwrap();
+    cmd_buf_builder.copy_image_to_buffer(image, buffer.clone()).unwrap();
+
+    upload.then_execute(queue, cmd_buf_builder.build().unwrap()).unwrap()
+        .then_signal_fence_and_flush().unwrap()
+        .wait(None).unwrap();
+
+    let pixels = buffer.read().unwrap();
+    let lit = pixels.chunks_exact(4).filter(|p| p[0] > 128).count();
+    assert!(lit > 100, "only {} pixels of the glyph were drawn", lit);
+}
@@ -0,0 +1,31 @@
+mod common;
+
+use imgui_vulkano_renderer::FramebufferCacheStats;
+
+const SIZE : u32 = 32;
+
+#[test]
+fn framebuffers_are_reused_per_target() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    let first = common::target(device.clone(), [SIZE; 2]);
+    let second = common::target(device.clone(), [SIZE; 2]);
+
+    for image in [&first, &first, &second, &first] {
+        let ui = ctx.frame();
+        ui.get_background_draw_list()
+            .add_rect([0.0, 0.0], [SIZE as f32 / 2.0; 2], [1.0; 4])
+            .filled(true)
+            .build();
+        let draw_data = ui.render();
+        common::render_into(&mut renderer, device.clone(), queue.clone(), image.clone(), draw_data);
+    }
+
+    assert_eq!(renderer.framebuffer_cache_stats(), FramebufferCacheStats { hits : 2, misses : 2, cached : 2 });
+
+    renderer.invalidate_framebuffers();
+    assert_eq!(renderer.framebuffer_cache_stats().cached, 0);
+}
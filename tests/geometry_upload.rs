@@ -0,0 +1,133 @@
+mod common;
+
+use std::sync::Arc;
+
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryCommandBuffer, SubpassContents};
+use vulkano::format::ClearValue;
+use vulkano::image::view::ImageView;
+use vulkano::render_pass::Framebuffer;
+use vulkano::sync::GpuFuture;
+
+use imgui_vulkano_renderer::{GeometryUpload, RendererOptions};
+
+const SIZE : u32 = 32;
+const GREEN : [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+const BLUE : [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+#[test]
+fn device_local_geometry_is_drawn_as_it_grows() {
+    let options = RendererOptions {
+        geometry_upload : GeometryUpload::DeviceLocal,
+        ..RendererOptions::default()
+    };
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup_with_options(SIZE, common::FORMAT, options) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    // a small frame, a frame that outgrows the buffers and a small one again that fits in them
+    for &rects in [1, 5_000, 1].iter() {
+        let ui = ctx.frame();
+        {
+            let draw_list = ui.get_background_draw_list();
+            for _ in 1 .. rects {
+                draw_list.add_rect([0.0, 0.0], [1.0, 1.0], BLUE).filled(true).build();
+            }
+            draw_list.add_rect([0.0, SIZE as f32 / 2.0], [SIZE as f32; 2], GREEN).filled(true).build();
+        }
+        // a second draw list, placed after the first one in the frame's buffers
+        ui.get_foreground_draw_list().add_rect([SIZE as f32 / 2.0, 0.0], [SIZE as f32, SIZE as f32 / 2.0], BLUE).filled(true).build();
+        let draw_data = ui.render();
+
+        let pixels = common::render(&mut renderer, device.clone(), queue.clone(), [SIZE; 2], draw_data);
+
+        assert_eq!(common::pixel(&pixels, SIZE, [SIZE / 4, 3 * SIZE / 4]), [0, 255, 0, 255], "{} rectangles", rects);
+        assert_eq!(common::pixel(&pixels, SIZE, [3 * SIZE / 4, SIZE / 4]), [0, 0, 255, 255], "{} rectangles", rects);
+        assert_eq!(common::pixel(&pixels, SIZE, [SIZE / 4, SIZE / 4]), [0, 0, 0, 0], "{} rectangles", rects);
+    }
+}
+
+#[test]
+fn geometry_uploaded_for_other_draw_data_is_not_used() {
+    // the upload is recorded for a frame with a single rectangle, but one with two is drawn
+    let pixels = match draw_after_upload(
+        |ui| {
+            ui.get_background_draw_list().add_rect([0.0, 0.0], [SIZE as f32 / 2.0, SIZE as f32], BLUE).filled(true).build();
+        },
+        |ui| {
+            let draw_list = ui.get_background_draw_list();
+            draw_list.add_rect([0.0, 0.0], [SIZE as f32 / 2.0, SIZE as f32], GREEN).filled(true).build();
+            draw_list.add_rect([SIZE as f32 / 2.0, 0.0], [SIZE as f32; 2], GREEN).filled(true).build();
+        },
+    ) {
+        Some(pixels) => pixels,
+        None => return,
+    };
+
+    assert_eq!(common::pixel(&pixels, SIZE, [SIZE / 4, SIZE / 2]), [0, 255, 0, 255]);
+    assert_eq!(common::pixel(&pixels, SIZE, [3 * SIZE / 4, SIZE / 2]), [0, 255, 0, 255]);
+}
+
+#[test]
+fn geometry_uploaded_for_moved_geometry_is_not_used() {
+    // the same number of vertices and indices, in other places
+    let pixels = match draw_after_upload(
+        |ui| {
+            ui.get_background_draw_list().add_rect([0.0, 0.0], [SIZE as f32 / 2.0, SIZE as f32], BLUE).filled(true).build();
+        },
+        |ui| {
+            ui.get_background_draw_list().add_rect([SIZE as f32 / 2.0, 0.0], [SIZE as f32; 2], GREEN).filled(true).build();
+        },
+    ) {
+        Some(pixels) => pixels,
+        None => return,
+    };
+
+    assert_eq!(common::pixel(&pixels, SIZE, [SIZE / 4, SIZE / 2]), [0, 0, 0, 0]);
+    assert_eq!(common::pixel(&pixels, SIZE, [3 * SIZE / 4, SIZE / 2]), [0, 255, 0, 255]);
+}
+
+/// Record the geometry upload of the frame built by `uploaded`, then draw the frame built by
+/// `drawn` with `draw_commands_in_subpass` and read the pixels back.
+fn draw_after_upload<U, D>(uploaded : U, drawn : D) -> Option<Vec<u8>>
+where U: FnOnce(&imgui::Ui), D: FnOnce(&imgui::Ui) {
+    let options = RendererOptions {
+        geometry_upload : GeometryUpload::DeviceLocal,
+        ..RendererOptions::default()
+    };
+    let (_lock, device, queue, mut ctx, mut renderer) = common::setup_with_options(SIZE, common::FORMAT, options)?;
+
+    let image = common::target(device.clone(), [SIZE; 2]);
+    let buffer = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::transfer_destination(), false, (0 .. SIZE * SIZE * 4).map(|_| 0u8)).unwrap();
+    let framebuffer = Arc::new(Framebuffer::start(renderer.subpass().render_pass().clone()).add(ImageView::new(image.clone()).unwrap()).unwrap().build().unwrap());
+
+    let mut cmd_buf_builder = AutoCommandBufferBuilder::primary(device, queue.family(), CommandBufferUsage::OneTimeSubmit).unwrap();
+    cmd_buf_builder.clear_color_image(image.clone(), [0.0; 4].into()).unwrap();
+    renderer.record_font_upload(&mut cmd_buf_builder).unwrap();
+
+    {
+        let ui = ctx.frame();
+        uploaded(&ui);
+        let draw_data = ui.render();
+        renderer.record_geometry_upload(&mut cmd_buf_builder, draw_data).unwrap();
+    }
+
+    let ui = ctx.frame();
+    drawn(&ui);
+    let draw_data = ui.render();
+
+    cmd_buf_builder.begin_render_pass(framebuffer, SubpassContents::Inline, vec![ClearValue::None]).unwrap();
+    renderer.draw_commands_in_subpass(&mut cmd_buf_builder, [SIZE; 2], draw_data).unwrap();
+    cmd_buf_builder
+        .end_render_pass().unwrap()
+        .copy_image_to_buffer(image, buffer.clone()).unwrap();
+
+    cmd_buf_builder.build().unwrap()
+        .execute(queue).unwrap()
+        .then_signal_fence_and_flush().unwrap()
+        .wait(None).unwrap();
+
+    let pixels = buffer.read().unwrap().to_vec();
+    Some(pixels)
+}
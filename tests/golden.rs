@@ -0,0 +1,162 @@
+//! Golden image tests: canonical imgui scenes are rendered on a headless CPU device such as
+//! lavapipe and compared with the reference images in `tests/golden`, which were rendered on
+//! lavapipe as well. Without a CPU device the tests are skipped.
+//!
+//! A scene without a reference image fails. To write the references, or update them after an
+//! intended rendering change, run the tests with `BLESS_GOLDEN=1` and check the new images in.
+//! When a scene doesn't match, its output is written next to the test binaries for inspection.
+
+mod common;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use imgui::{im_str, ChildWindow, Condition, Image, TextureId, Window};
+use imgui_vulkano_renderer::Renderer;
+use vulkano::device::{Device, Queue};
+
+/// The largest difference allowed in each channel of a pixel, to allow for rounding differences
+/// between lavapipe versions.
+const TOLERANCE : u8 = 8;
+
+/// The number of frames rendered before the output is captured, so auto-sized windows settle.
+const FRAMES : usize = 3;
+
+/// Render a scene and compare the last frame against the reference image `name`.
+/// `setup` runs once after the renderer is created, and its result is passed to `scene`,
+/// which builds each frame.
+fn golden<T, S, F>(name : &str, size : [u32; 2], setup : S, mut scene : F)
+where S: FnOnce(&mut Renderer, Arc<Device>, Arc<Queue>) -> T, F: FnMut(&imgui::Ui, &T) {
+    let (device, queue) = match common::software_headless() {
+        Some(dq) => dq,
+        None => return,
+    };
+    let _lock = common::lock_imgui();
+
+    let mut ctx = common::context([size[0] as f32, size[1] as f32]);
+    let mut renderer = Renderer::init(&mut ctx, device.clone(), queue.clone(), common::FORMAT).unwrap();
+
+    let state = setup(&mut renderer, device, queue.clone());
+
+    let mut pixels = Vec::new();
+    for _ in 0 .. FRAMES {
+        let ui = ctx.frame();
+        scene(&ui, &state);
+        let draw_data = ui.render();
+        pixels = renderer.render_offscreen(queue.clone(), size, common::FORMAT, draw_data).unwrap();
+    }
+
+    compare(name, image::RgbaImage::from_raw(size[0], size[1], pixels).unwrap());
+}
+
+fn compare(name : &str, actual : image::RgbaImage) {
+    let reference = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(format!("{}.png", name));
+
+    if std::env::var_os("BLESS_GOLDEN").is_some() {
+        std::fs::create_dir_all(reference.parent().unwrap()).unwrap();
+        actual.save(&reference).unwrap();
+        eprintln!("wrote reference image {}", reference.display());
+        return;
+    }
+
+    assert!(
+        reference.exists(),
+        "the reference image {} is missing, run the tests with BLESS_GOLDEN=1 to write it",
+        reference.display(),
+    );
+
+    let expected = image::open(&reference).unwrap().to_rgba8();
+    assert_eq!(expected.dimensions(), actual.dimensions(), "size of {} changed", name);
+
+    let mismatched = expected.pixels().zip(actual.pixels())
+        .filter(|(e, a)| e.0.iter().zip(a.0.iter()).any(|(&e, &a)| (e as i16 - a as i16).abs() > TOLERANCE as i16))
+        .count();
+
+    if mismatched > 0 {
+        let output = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.png", name));
+        actual.save(&output).unwrap();
+        panic!(
+            "{} of {} pixels differ from {} by more than {}, the output was written to {}",
+            mismatched, expected.width() * expected.height(), reference.display(), TOLERANCE, output.display(),
+        );
+    }
+}
+
+#[test]
+fn demo_window() {
+    golden("demo_window", [1280, 720], |_, _, _| (), |ui, _| {
+        ui.show_demo_window(&mut true);
+    });
+}
+
+#[test]
+fn custom_textures() {
+    let setup = |renderer : &mut Renderer, device, queue| {
+        // a checkerboard with a gradient, so sampling and texture coordinates are visible
+        let pixels : Vec<u8> = (0 .. 32 * 32).flat_map(|i| {
+            let (x, y) = (i % 32, i / 32);
+            let checker = if (x / 4 + y / 4) % 2 == 0 { 255 } else { 64 };
+            vec![(x * 8) as u8, (y * 8) as u8, checker, 255]
+        }).collect();
+
+        renderer.textures().insert(common::texture(device, queue, [32, 32], &pixels))
+    };
+
+    golden("custom_textures", [256, 256], setup, |ui, &texture_id : &TextureId| {
+        Window::new(im_str!("Custom textures"))
+            .position([8.0, 8.0], Condition::Always)
+            .size([240.0, 240.0], Condition::Always)
+            .build(ui, || {
+                Image::new(texture_id, [64.0, 64.0]).build(ui);
+                Image::new(texture_id, [160.0, 96.0])
+                    .uv0([0.25, 0.25])
+                    .uv1([0.75, 0.75])
+                    .tint_col([1.0, 0.5, 0.5, 0.75])
+                    .build(ui);
+            });
+    });
+}
+
+#[test]
+fn clipped_child_windows() {
+    golden("clipped_child_windows", [256, 256], |_, _, _| (), |ui, _| {
+        Window::new(im_str!("Clipping"))
+            .position([8.0, 8.0], Condition::Always)
+            .size([240.0, 240.0], Condition::Always)
+            .build(ui, || {
+                ChildWindow::new("outer")
+                    .size([200.0, 150.0])
+                    .border(true)
+                    .build(ui, || {
+                        for i in 0 .. 20 {
+                            ui.text(format!("Line {} of text that is too long for the child window", i));
+                        }
+
+                        ChildWindow::new("inner")
+                            .size([100.0, 60.0])
+                            .border(true)
+                            .build(ui, || {
+                                for i in 0 .. 10 {
+                                    ui.text(format!("Nested line {}", i));
+                                }
+                            });
+                    });
+            });
+    });
+}
+
+#[test]
+fn large_vertex_count() {
+    golden("large_vertex_count", [256, 256], |_, _, _| (), |ui, _| {
+        let draw_list = ui.get_background_draw_list();
+
+        // 256 * 80 quads push the draw list past the 16-bit index range
+        for y in 0 .. 80 {
+            for x in 0 .. 256 {
+                let color = [x as f32 / 255.0, y as f32 / 79.0, 1.0 - x as f32 / 255.0, 1.0];
+                let min = [x as f32, y as f32 * 3.2];
+                draw_list.add_rect(min, [min[0] + 1.0, min[1] + 3.2], color).filled(true).build();
+            }
+        }
+    });
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use vulkano::format::Format;
+
+use imgui_vulkano_renderer::{HdrOutput, RendererOptions};
+
+const SIZE : u32 = 16;
+const WHITE : [f32; 4] = [1.0; 4];
+
+/// Draw a white rectangle over a target of `format` and return the bytes of the center pixel.
+fn render_white(format : Format, hdr_output : HdrOutput) -> Option<Vec<u8>> {
+    let options = RendererOptions {
+        hdr_output,
+        ..RendererOptions::default()
+    };
+    let (_lock, device, queue, mut ctx, mut renderer) = common::setup_with_options(SIZE, format, options)?;
+
+    let ui = ctx.frame();
+    ui.get_background_draw_list()
+        .add_rect([0.0, 0.0], [SIZE as f32; 2], WHITE)
+        .filled(true)
+        .build();
+    let draw_data = ui.render();
+
+    let image = common::target_with_format(device.clone(), [SIZE; 2], format);
+    let pixels = common::render_into(&mut renderer, device, queue, image, draw_data);
+
+    let texel_size = format.size().unwrap();
+    let i = ((SIZE / 2 * SIZE + SIZE / 2) as usize) * texel_size;
+    Some(pixels[i .. i + texel_size].to_vec())
+}
+
+/// Decode a little-endian IEEE half float.
+fn half(bytes : &[u8]) -> f32 {
+    let bits = u16::from_le_bytes([bytes[0], bytes[1]]);
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32 / 1024.0;
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-14),
+        _ => sign * (1.0 + mantissa) * 2f32.powi(exponent - 15),
+    }
+}
+
+#[test]
+fn scrgb_white_is_paper_white() {
+    let pixel = match render_white(Format::R16G16B16A16Sfloat, HdrOutput::ScRgb { paper_white : 160.0 }) {
+        Some(pixel) => pixel,
+        None => return,
+    };
+
+    // 160 nits is twice the 80 nits of 1.0
+    for channel in 0 .. 3 {
+        let value = half(&pixel[channel * 2 ..]);
+        assert!((value - 2.0).abs() < 0.01, "channel {}: expected 2.0, got {}", channel, value);
+    }
+    assert!((half(&pixel[6 ..]) - 1.0).abs() < 0.01);
+}
+
+#[test]
+fn pq_white_is_encoded_paper_white() {
+    let pixel = match render_white(common::FORMAT, HdrOutput::Pq { paper_white : 203.0 }) {
+        Some(pixel) => pixel,
+        None => return,
+    };
+
+    // 203 nits encode to 0.58 on the PQ curve
+    for (channel, &value) in pixel[.. 3].iter().enumerate() {
+        assert!((value as i16 - 148).abs() <= 1, "channel {}: expected 148, got {}", channel, value);
+    }
+    assert_eq!(pixel[3], 255);
+}
+
+#[test]
+fn sdr_output_is_unchanged() {
+    if let Some(pixel) = render_white(common::FORMAT, HdrOutput::Sdr) {
+        assert_eq!(pixel, vec![255; 4]);
+    }
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use imgui_vulkano_renderer::{AttachmentLoad, RendererOptions};
+
+const SIZE : u32 = 64;
+const GREEN : [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+
+#[test]
+fn clear_load_op_clears_target() {
+    let options = RendererOptions {
+        load : AttachmentLoad::Clear([0.0, 0.0, 1.0, 1.0]),
+        ..RendererOptions::default()
+    };
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup_with_options(SIZE, common::FORMAT, options) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    let ui = ctx.frame();
+    ui.get_background_draw_list()
+        .add_rect([0.0, 0.0], [SIZE as f32 / 2.0, SIZE as f32], GREEN)
+        .filled(true)
+        .build();
+    let draw_data = ui.render();
+
+    let pixels = common::render(&mut renderer, device, queue, [SIZE; 2], draw_data);
+
+    assert_eq!(common::pixel(&pixels, SIZE, [SIZE / 4, SIZE / 2]), [0, 255, 0, 255]);
+    assert_eq!(common::pixel(&pixels, SIZE, [3 * SIZE / 4, SIZE / 2]), [0, 0, 255, 255]);
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use imgui_vulkano_renderer::{AttachmentLoad, Renderer, RendererError, RendererOptions};
+
+const SIZE : u32 = 64;
+const GREEN : [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+
+#[test]
+fn multisampled_edges_are_resolved() {
+    let options = RendererOptions {
+        samples : 4,
+        load : AttachmentLoad::Clear([0.0; 4]),
+        ..RendererOptions::default()
+    };
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup_with_options(SIZE, common::FORMAT, options) {
+        Some(setup) => setup,
+        None => return,
+    };
+    // without imgui's fringes, any partial coverage comes from multisampling
+    ctx.style_mut().anti_aliased_fill = false;
+
+    let ui = ctx.frame();
+    ui.get_background_draw_list()
+        .add_triangle([0.0, 0.0], [SIZE as f32, 0.0], [0.0, SIZE as f32], GREEN)
+        .filled(true)
+        .build();
+    let draw_data = ui.render();
+
+    let pixels = common::render(&mut renderer, device, queue, [SIZE; 2], draw_data);
+
+    assert_eq!(common::pixel(&pixels, SIZE, [4, 4]), [0, 255, 0, 255]);
+    assert_eq!(common::pixel(&pixels, SIZE, [SIZE - 4, SIZE - 4]), [0, 0, 0, 0]);
+
+    let partial = (0 .. SIZE).any(|x| {
+        let [_, g, _, _] = common::pixel(&pixels, SIZE, [x, SIZE - 1 - x]);
+        g > 0 && g < 255
+    });
+    assert!(partial, "no partially covered pixels along the triangle's edge");
+}
+
+#[test]
+fn bad_sample_counts_are_rejected() {
+    let (device, queue) = match common::headless() {
+        Some(dq) => dq,
+        None => return,
+    };
+    let _lock = common::lock_imgui();
+
+    for &samples in [0, 3, 5, 128].iter() {
+        let mut ctx = common::context([SIZE as f32; 2]);
+        let options = RendererOptions {
+            samples,
+            load : AttachmentLoad::Clear([0.0; 4]),
+            ..RendererOptions::default()
+        };
+        match Renderer::init_with_options(&mut ctx, device.clone(), queue.clone(), common::FORMAT, options) {
+            Err(RendererError::UnsupportedSampleCount(s)) => assert_eq!(s, samples),
+            Err(e) => panic!("{} samples: unexpected error {}", samples, e),
+            Ok(_) => panic!("{} samples were accepted", samples),
+        }
+    }
+}
+
+#[test]
+fn loading_a_multisampled_target_is_rejected() {
+    let (device, queue) = match common::headless() {
+        Some(dq) => dq,
+        None => return,
+    };
+    let _lock = common::lock_imgui();
+
+    let mut ctx = common::context([SIZE as f32; 2]);
+    let options = RendererOptions {
+        samples : 4,
+        load : AttachmentLoad::Load,
+        ..RendererOptions::default()
+    };
+    let result = Renderer::init_with_options(&mut ctx, device, queue, common::FORMAT, options);
+    assert!(matches!(result, Err(RendererError::LoadWithMultisampling)));
+}
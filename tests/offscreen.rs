@@ -0,0 +1,39 @@
+mod common;
+
+use imgui_vulkano_renderer::{RendererError, RendererOptions};
+use vulkano::format::Format;
+
+const SIZE : u32 = 16;
+const RED : [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+#[test]
+fn bgra_images_are_read_back_as_rgba() {
+    let (_lock, _device, queue, mut ctx, mut renderer) = match common::setup_with_options(SIZE, Format::B8G8R8A8Unorm, RendererOptions::default()) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    for _ in 0 .. 2 {
+        let ui = ctx.frame();
+        ui.get_background_draw_list()
+            .add_rect([0.0, 0.0], [SIZE as f32 / 2.0, SIZE as f32], RED)
+            .filled(true)
+            .build();
+        let draw_data = ui.render();
+
+        let pixels = renderer.render_offscreen(queue.clone(), [SIZE; 2], Format::B8G8R8A8Unorm, draw_data).unwrap();
+
+        assert_eq!(pixels.len(), (SIZE * SIZE * 4) as usize);
+        assert_eq!(common::pixel(&pixels, SIZE, [SIZE / 4, SIZE / 2]), [255, 0, 0, 255]);
+        assert_eq!(common::pixel(&pixels, SIZE, [3 * SIZE / 4, SIZE / 2]), [0, 0, 0, 0]);
+    }
+
+    assert_eq!(renderer.framebuffer_cache_stats().misses, 1);
+
+    let ui = ctx.frame();
+    let draw_data = ui.render();
+    match renderer.render_offscreen(queue, [SIZE; 2], Format::R16G16B16A16Sfloat, draw_data) {
+        Err(RendererError::UnsupportedFormat(Format::R16G16B16A16Sfloat)) => {},
+        r => panic!("expected UnsupportedFormat, got {:?}", r.map(|_| ())),
+    }
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use imgui_vulkano_renderer::{AttachmentLoad, BlendMode, RendererOptions};
+
+const SIZE : u32 = 64;
+const HALF_RED : [f32; 4] = [1.0, 0.0, 0.0, 0.5];
+
+#[test]
+fn transparent_target_holds_premultiplied_colors() {
+    let options = RendererOptions {
+        load : AttachmentLoad::Clear([0.0; 4]),
+        premultiplied_alpha : true,
+        ..RendererOptions::default()
+    };
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup_with_options(SIZE, common::FORMAT, options) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    let premultiplied = common::solid_texture(device.clone(), queue.clone(), [128, 0, 0, 128]).with_blend_mode(BlendMode::Premultiplied);
+    let texture_id = renderer.textures().insert(premultiplied);
+
+    // half transparent red drawn with a vertex color on the left and a premultiplied texture on the right
+    let ui = ctx.frame();
+    ui.get_background_draw_list()
+        .add_rect([0.0, 0.0], [SIZE as f32 / 2.0, SIZE as f32], HALF_RED)
+        .filled(true)
+        .build();
+    common::add_image(&ui, texture_id, [SIZE as f32 / 2.0, 0.0], [SIZE as f32; 2]);
+    let draw_data = ui.render();
+
+    let pixels = common::render(&mut renderer, device, queue, [SIZE; 2], draw_data);
+
+    for x in [SIZE / 4, 3 * SIZE / 4].iter() {
+        let actual = common::pixel(&pixels, SIZE, [*x, SIZE / 2]);
+        let close = actual.iter().zip([128, 0, 0, 128].iter()).all(|(&a, &e)| (a as i16 - e as i16).abs() <= 1);
+        assert!(close, "expected [128, 0, 0, 128] at x = {}, got {:?}", x, actual);
+    }
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use imgui::TextureId;
+use imgui_vulkano_renderer::RendererError;
+
+const SIZE : u32 = 16;
+
+#[test]
+fn textures_are_reuploaded_with_their_ids() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+    let (new_device, new_queue) = common::headless().unwrap();
+
+    let texture_id = renderer.textures().insert(common::solid_texture(device.clone(), queue.clone(), [255, 0, 0, 255]));
+
+    // the font atlas is recreated by the renderer, not passed to `reupload`
+    let font_texture_id = renderer.font_texture_id();
+    let mut reuploaded = Vec::new();
+    renderer.recreate_on_device(&mut ctx, new_device.clone(), new_queue.clone(), vec![texture_id, font_texture_id], |id, _| {
+        reuploaded.push(id);
+        Ok(common::solid_texture(new_device.clone(), new_queue.clone(), [0, 255, 0, 255]))
+    }).unwrap();
+
+    assert_eq!(reuploaded, vec![texture_id]);
+
+    let ui = ctx.frame();
+    common::add_image(&ui, texture_id, [0.0, 0.0], [SIZE as f32; 2]);
+    let draw_data = ui.render();
+
+    let pixels = common::render(&mut renderer, new_device, new_queue, [SIZE; 2], draw_data);
+
+    assert_eq!(common::pixel(&pixels, SIZE, [SIZE / 2; 2]), [0, 255, 0, 255]);
+}
+
+#[test]
+fn failed_recreation_leaves_the_renderer_unchanged() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+    let (new_device, new_queue) = common::headless().unwrap();
+
+    let first = renderer.textures().insert(common::solid_texture(device.clone(), queue.clone(), [255, 0, 0, 255]));
+    let second = renderer.textures().insert(common::solid_texture(device.clone(), queue.clone(), [255, 0, 0, 255]));
+
+    // the second reupload fails after the first one succeeded
+    let result = renderer.recreate_on_device(&mut ctx, new_device.clone(), new_queue.clone(), vec![first, second], |id, _| {
+        if id == second {
+            return Err(RendererError::BadTexture(id));
+        }
+        Ok(common::solid_texture(new_device.clone(), new_queue.clone(), [0, 255, 0, 255]))
+    });
+    assert!(matches!(result, Err(RendererError::BadTexture(id)) if id == second));
+
+    // an unknown ID is rejected before anything is reuploaded
+    let unknown = TextureId::from(usize::MAX);
+    let result = renderer.recreate_on_device(&mut ctx, new_device, new_queue, vec![unknown], |_, _| unreachable!());
+    assert!(matches!(result, Err(RendererError::BadTexture(id)) if id == unknown));
+
+    // still drawn on the old device, with the old texture
+    let ui = ctx.frame();
+    common::add_image(&ui, first, [0.0, 0.0], [SIZE as f32; 2]);
+    let draw_data = ui.render();
+
+    let pixels = common::render(&mut renderer, device, queue, [SIZE; 2], draw_data);
+
+    assert_eq!(common::pixel(&pixels, SIZE, [SIZE / 2; 2]), [255, 0, 0, 255]);
+}
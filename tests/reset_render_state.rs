@@ -0,0 +1,67 @@
+mod common;
+
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use imgui::{im_str, sys, Condition, Window, WindowFlags};
+
+const SIZE : u32 = 64;
+const GREEN : [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+const BLUE : [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+static CALLBACK_CALLS : AtomicUsize = AtomicUsize::new(0);
+
+unsafe extern "C" fn count_calls(_ : *const sys::ImDrawList, _ : *const sys::ImDrawCmd) {
+    CALLBACK_CALLS.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+fn draws_after_reset_render_state() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    let ui = ctx.frame();
+    // a window without decorations over the whole display, so the callbacks are in the same
+    // draw list as the rectangles. The reset has nothing to restore, as every draw re-binds the
+    // renderer's state, but the geometry after it must still be drawn.
+    Window::new(im_str!("scene"))
+        .position([0.0, 0.0], Condition::Always)
+        .size([SIZE as f32; 2], Condition::Always)
+        .flags(WindowFlags::NO_DECORATION | WindowFlags::NO_BACKGROUND)
+        .build(&ui, || {
+            let draw_list = ui.get_window_draw_list();
+
+            draw_list.with_clip_rect([0.0, 0.0], [SIZE as f32; 2], || {
+                draw_list.add_rect([0.0, 0.0], [SIZE as f32 / 2.0, SIZE as f32], GREEN).filled(true).build();
+            });
+
+            unsafe {
+                let raw = sys::igGetWindowDrawList();
+                sys::ImDrawList_AddCallback(raw, Some(count_calls), ptr::null_mut());
+                // imgui's `ImDrawCallback_ResetRenderState`
+                let reset = std::mem::transmute::<isize, unsafe extern "C" fn(*const sys::ImDrawList, *const sys::ImDrawCmd)>(-1);
+                sys::ImDrawList_AddCallback(raw, Some(reset), ptr::null_mut());
+            }
+
+            draw_list.with_clip_rect([SIZE as f32 / 2.0, 0.0], [SIZE as f32; 2], || {
+                draw_list.add_rect([0.0, 0.0], [SIZE as f32; 2], BLUE).filled(true).build();
+            });
+        });
+    let draw_data = ui.render();
+
+    let pixels = common::render(&mut renderer, device, queue, [SIZE; 2], draw_data);
+
+    assert_eq!(CALLBACK_CALLS.load(Ordering::SeqCst), 1);
+    for y in 0 .. SIZE {
+        for x in 0 .. SIZE {
+            let expected = if x < SIZE / 2 {
+                [0, 255, 0, 255]
+            } else {
+                [0, 0, 255, 255]
+            };
+            assert_eq!(common::pixel(&pixels, SIZE, [x, y]), expected, "pixel [{}, {}]", x, y);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use std::sync::Arc;
+
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryCommandBuffer, SubpassContents};
+use vulkano::image::{AttachmentImage, ImageUsage};
+use vulkano::image::view::ImageView;
+use vulkano::render_pass::{Framebuffer, Subpass};
+use vulkano::sync::GpuFuture;
+
+use imgui_vulkano_renderer::{Renderer, RendererError, RendererOptions};
+
+const SIZE : u32 = 64;
+const GREEN : [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+
+#[test]
+fn draws_into_caller_render_pass() {
+    let (device, queue) = match common::headless() {
+        Some(dq) => dq,
+        None => return,
+    };
+    let _lock = common::lock_imgui();
+
+    // a "scene" subpass followed by a UI subpass, with the clear done by the render pass
+    let render_pass = Arc::new(vulkano::ordered_passes_renderpass!(
+        device.clone(),
+        attachments: {
+            color: {
+                load: Clear,
+                store: Store,
+                format: common::FORMAT,
+                samples: 1,
+            }
+        },
+        passes: [
+            { color: [color], depth_stencil: {}, input: [] },
+            { color: [color], depth_stencil: {}, input: [] }
+        ]
+    ).unwrap());
+
+    let mut ctx = common::context([SIZE as f32; 2]);
+    let mut renderer = Renderer::init_with_subpass(&mut ctx, device.clone(), queue.clone(), Subpass::from(render_pass.clone(), 1).unwrap(), RendererOptions::default()).unwrap();
+
+    assert_eq!(renderer.subpass().index(), 1);
+
+    let ui = ctx.frame();
+    ui.get_background_draw_list()
+        .add_rect([0.0, 0.0], [SIZE as f32 / 2.0, SIZE as f32], GREEN)
+        .filled(true)
+        .build();
+    let draw_data = ui.render();
+
+    let image = AttachmentImage::with_usage(
+        device.clone(),
+        [SIZE; 2],
+        common::FORMAT,
+        ImageUsage {
+            transfer_source : true,
+            ..ImageUsage::color_attachment()
+        },
+    ).unwrap();
+    let buffer = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::transfer_destination(), false, (0 .. SIZE * SIZE * 4).map(|_| 0u8)).unwrap();
+    let framebuffer = Arc::new(Framebuffer::start(render_pass).add(ImageView::new(image.clone()).unwrap()).unwrap().build().unwrap());
+
+    let mut cmd_buf_builder = AutoCommandBufferBuilder::primary(device, queue.family(), CommandBufferUsage::OneTimeSubmit).unwrap();
+    renderer.record_font_upload(&mut cmd_buf_builder).unwrap();
+    cmd_buf_builder
+        .begin_render_pass(framebuffer, SubpassContents::Inline, vec![[0.0, 0.0, 1.0, 1.0].into()]).unwrap()
+        .next_subpass(SubpassContents::Inline).unwrap();
+    renderer.draw_commands_in_subpass(&mut cmd_buf_builder, [SIZE; 2], draw_data).unwrap();
+    cmd_buf_builder
+        .end_render_pass().unwrap()
+        .copy_image_to_buffer(image, buffer.clone()).unwrap();
+
+    cmd_buf_builder.build().unwrap()
+        .execute(queue).unwrap()
+        .then_signal_fence_and_flush().unwrap()
+        .wait(None).unwrap();
+
+    let pixels = buffer.read().unwrap();
+    assert_eq!(common::pixel(&pixels, SIZE, [SIZE / 4, SIZE / 2]), [0, 255, 0, 255]);
+    assert_eq!(common::pixel(&pixels, SIZE, [3 * SIZE / 4, SIZE / 2]), [0, 0, 255, 255]);
+}
+
+#[test]
+fn drawing_before_the_font_upload_is_an_error() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    let ui = ctx.frame();
+    let draw_data = ui.render();
+
+    // the atlas image is only written by the recorded upload
+    let mut cmd_buf_builder = AutoCommandBufferBuilder::primary(device, queue.family(), CommandBufferUsage::OneTimeSubmit).unwrap();
+    let result = renderer.draw_commands_in_subpass(&mut cmd_buf_builder, [SIZE; 2], draw_data);
+    assert!(matches!(result, Err(RendererError::FontAtlasNotUploaded)));
+
+    renderer.record_font_upload(&mut cmd_buf_builder).unwrap();
+    let ui = ctx.frame();
+    let draw_data = ui.render();
+    assert!(renderer.draw_commands_in_subpass(&mut cmd_buf_builder, [SIZE; 2], draw_data).is_ok());
+}
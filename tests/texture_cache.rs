@@ -0,0 +1,106 @@
+mod common;
+
+use imgui_vulkano_renderer::DescriptorSetCacheStats;
+
+const SIZE : u32 = 16;
+
+#[test]
+fn replaced_textures_are_drawn() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    let red = common::solid_texture(device.clone(), queue.clone(), [255, 0, 0, 255]);
+    let green = common::solid_texture(device.clone(), queue.clone(), [0, 255, 0, 255]);
+    let texture_id = renderer.textures().insert(red);
+
+    let mut colors = Vec::new();
+    for frame in 0 .. 3 {
+        if frame == 2 {
+            renderer.textures().replace(texture_id, green.clone());
+        }
+
+        let ui = ctx.frame();
+        common::add_image(&ui, texture_id, [0.0, 0.0], [SIZE as f32; 2]);
+        let draw_data = ui.render();
+
+        let pixels = common::render(&mut renderer, device.clone(), queue.clone(), [SIZE; 2], draw_data);
+        colors.push(common::pixel(&pixels, SIZE, [SIZE / 2; 2]));
+    }
+
+    assert_eq!(colors, vec![[255, 0, 0, 255], [255, 0, 0, 255], [0, 255, 0, 255]]);
+}
+
+#[test]
+fn descriptor_sets_are_reused_across_draws_and_frames() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    let red = common::solid_texture(device.clone(), queue.clone(), [255, 0, 0, 255]);
+    let green = common::solid_texture(device.clone(), queue.clone(), [0, 255, 0, 255]);
+    let texture_id = renderer.textures().insert(red);
+
+    // two draw commands with the texture, split by a clip rect, in each of two frames
+    for _ in 0 .. 2 {
+        let ui = ctx.frame();
+        {
+            let draw_list = ui.get_background_draw_list();
+            draw_list.with_clip_rect([0.0, 0.0], [SIZE as f32 / 2.0, SIZE as f32], || {
+                common::add_image(&ui, texture_id, [0.0, 0.0], [SIZE as f32; 2]);
+            });
+            draw_list.with_clip_rect([SIZE as f32 / 2.0, 0.0], [SIZE as f32; 2], || {
+                common::add_image(&ui, texture_id, [0.0, 0.0], [SIZE as f32; 2]);
+            });
+        }
+        let draw_data = ui.render();
+        common::render(&mut renderer, device.clone(), queue.clone(), [SIZE; 2], draw_data);
+    }
+
+    assert_eq!(renderer.descriptor_set_cache_stats(), DescriptorSetCacheStats { hits : 3, misses : 1, cached : 1 });
+
+    // a replaced texture needs a new set, a removed one drops its set
+    renderer.textures().replace(texture_id, green);
+    let ui = ctx.frame();
+    common::add_image(&ui, texture_id, [0.0, 0.0], [SIZE as f32; 2]);
+    let draw_data = ui.render();
+    common::render(&mut renderer, device.clone(), queue.clone(), [SIZE; 2], draw_data);
+
+    assert_eq!(renderer.descriptor_set_cache_stats(), DescriptorSetCacheStats { hits : 3, misses : 2, cached : 1 });
+
+    renderer.textures().remove(texture_id);
+    let ui = ctx.frame();
+    let draw_data = ui.render();
+    common::render(&mut renderer, device, queue, [SIZE; 2], draw_data);
+
+    assert_eq!(renderer.descriptor_set_cache_stats().cached, 0);
+}
+
+#[test]
+fn font_atlas_is_in_the_registry() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    let font_texture_id = renderer.font_texture_id();
+    assert_eq!(ctx.fonts().tex_id, font_texture_id);
+    assert!(renderer.textures().get(font_texture_id).is_some());
+
+    // solid fills sample the atlas, so replacing it tints them
+    let green = common::solid_texture(device.clone(), queue.clone(), [0, 255, 0, 255]);
+    renderer.textures().replace(font_texture_id, green);
+
+    let ui = ctx.frame();
+    ui.get_background_draw_list()
+        .add_rect([0.0, 0.0], [SIZE as f32; 2], [1.0; 4])
+        .filled(true)
+        .build();
+    let draw_data = ui.render();
+
+    let pixels = common::render(&mut renderer, device, queue, [SIZE; 2], draw_data);
+
+    assert_eq!(common::pixel(&pixels, SIZE, [SIZE / 2; 2]), [0, 255, 0, 255]);
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use vulkano::format::Format;
+
+use imgui_vulkano_renderer::RendererOptions;
+
+const SIZE : u32 = 16;
+// 128 in imgui's 8-bit vertex colors
+const GRAY : [f32; 4] = [128.0 / 255.0, 128.0 / 255.0, 128.0 / 255.0, 1.0];
+
+/// Draw a gray rectangle over a target of `format` and return the red byte written to it.
+fn render_gray(format : Format, options : RendererOptions) -> Option<u8> {
+    let (_lock, device, queue, mut ctx, mut renderer) = common::setup_with_options(SIZE, format, options)?;
+
+    let ui = ctx.frame();
+    ui.get_background_draw_list()
+        .add_rect([0.0, 0.0], [SIZE as f32; 2], GRAY)
+        .filled(true)
+        .build();
+    let draw_data = ui.render();
+
+    let image = common::target_with_format(device.clone(), [SIZE; 2], format);
+    let pixels = common::render_into(&mut renderer, device, queue, image, draw_data);
+
+    Some(common::pixel(&pixels, SIZE, [SIZE / 2; 2])[0])
+}
+
+fn assert_close(actual : Option<u8>, expected : u8) {
+    if let Some(actual) = actual {
+        assert!((actual as i16 - expected as i16).abs() <= 1, "expected {}, got {}", expected, actual);
+    }
+}
+
+#[test]
+fn srgb_targets_show_imgui_colors() {
+    assert_close(render_gray(Format::R8G8B8A8Srgb, RendererOptions::default()), 128);
+}
+
+#[test]
+fn unorm_targets_show_imgui_colors() {
+    assert_close(render_gray(common::FORMAT, RendererOptions::default()), 128);
+}
+
+#[test]
+fn conversion_can_be_turned_off() {
+    let options = RendererOptions {
+        linearize_vertex_colors : Some(false),
+        ..RendererOptions::default()
+    };
+    // 128 / 255 is encoded again as sRGB
+    assert_close(render_gray(Format::R8G8B8A8Srgb, options), 188);
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use imgui::BackendFlags;
+
+const SIZE : u32 = 64;
+const GREEN : [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+const RED : [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+#[test]
+fn draw_list_past_u16_range_renders_intact() {
+    let (_lock, device, queue, mut ctx, mut renderer) = match common::setup(SIZE) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    assert!(ctx.io().backend_flags.contains(BackendFlags::RENDERER_HAS_VTX_OFFSET));
+
+    let ui = ctx.frame();
+    {
+        let draw_list = ui.get_background_draw_list();
+
+        // each filled rectangle adds 4 vertices, so this pushes the list well past 65535 vertices
+        for _ in 0 .. 17_000 {
+            draw_list.add_rect([0.0, 0.0], [1.0, 1.0], RED).filled(true).build();
+        }
+
+        // only reachable through a non-zero vertex offset
+        draw_list.add_rect([0.0, SIZE as f32 / 2.0], [SIZE as f32; 2], GREEN).filled(true).build();
+    }
+    let draw_data = ui.render();
+
+    let vtx_count : usize = draw_data.draw_lists().map(|list| list.vtx_buffer().len()).sum();
+    assert!(vtx_count > u16::MAX as usize);
+
+    let pixels = common::render(&mut renderer, device, queue, [SIZE; 2], draw_data);
+
+    assert_eq!(common::pixel(&pixels, SIZE, [0, 0]), [255, 0, 0, 255]);
+    assert_eq!(common::pixel(&pixels, SIZE, [SIZE / 2, SIZE / 4]), [0, 0, 0, 0]);
+    for y in SIZE / 2 .. SIZE {
+        for x in 0 .. SIZE {
+            assert_eq!(common::pixel(&pixels, SIZE, [x, y]), [0, 255, 0, 255], "pixel [{}, {}]", x, y);
+        }
+    }
+}